@@ -0,0 +1,26 @@
+use std::path::PathBuf;
+
+#[path = "src/year2019/intcode/compile.rs"]
+mod compile;
+
+fn main() {
+    let compile_rs = "src/year2019/intcode/compile.rs";
+    println!("cargo:rerun-if-changed=build.rs");
+    println!("cargo:rerun-if-changed={}", compile_rs);
+
+    let mut source = String::new();
+    for day in &["day05", "day07", "day09"] {
+        let path = format!("inputs/year2019/{}.txt", day);
+        println!("cargo:rerun-if-changed={}", path);
+        let code = compile::decode(&std::fs::read_to_string(&path).unwrap());
+        source.push_str(&compile::translate(day, &code));
+    }
+    // exercises the fallback when a program rewrites its own instructions
+    source.push_str(&compile::translate(
+        "patched",
+        &[1101, 40, 2, 5, 104, 5, 99],
+    ));
+
+    let out = PathBuf::from(std::env::var_os("OUT_DIR").unwrap()).join("intcode_aot.rs");
+    std::fs::write(out, source).unwrap();
+}
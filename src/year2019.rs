@@ -1,4 +1,9 @@
+pub mod canvas;
+pub mod combinatorics;
 pub mod intcode;
+pub mod ocr;
+#[cfg(test)]
+mod testing;
 
 pub mod day01;
 pub mod day02;
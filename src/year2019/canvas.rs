@@ -0,0 +1,187 @@
+use std::ops::Range;
+
+/// Monochrome bitmap in screen coordinates, with row 0 at the top
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Canvas {
+    width: usize,
+    height: usize,
+    pixels: Vec<bool>,
+}
+
+impl Canvas {
+    pub fn new(width: usize, height: usize) -> Canvas {
+        Canvas {
+            width,
+            height,
+            pixels: vec![false; width * height],
+        }
+    }
+
+    /// Dense rows, padding short ones with unlit pixels
+    pub fn from_rows<R: AsRef<[bool]>>(rows: &[R]) -> Canvas {
+        let width = rows.iter().map(|row| row.as_ref().len()).max().unwrap_or(0);
+        let mut canvas = Canvas::new(width, rows.len());
+        for (y, row) in rows.iter().enumerate() {
+            for (x, &lit) in row.as_ref().iter().enumerate() {
+                canvas.set(x, y, lit);
+            }
+        }
+        canvas
+    }
+
+    /// Lit points anywhere on the plane, cropped to their bounding box. `y`
+    /// grows downwards; flip the result for y-up coordinates.
+    pub fn from_points<I: IntoIterator<Item = (isize, isize)>>(points: I) -> Canvas {
+        let points = points.into_iter().collect::<Vec<_>>();
+        let min_x = points.iter().map(|&(x, _)| x).min().unwrap_or(0);
+        let max_x = points.iter().map(|&(x, _)| x).max().unwrap_or(-1);
+        let min_y = points.iter().map(|&(_, y)| y).min().unwrap_or(0);
+        let max_y = points.iter().map(|&(_, y)| y).max().unwrap_or(-1);
+        let mut canvas = Canvas::new((max_x - min_x + 1) as usize, (max_y - min_y + 1) as usize);
+        for (x, y) in points {
+            canvas.set((x - min_x) as usize, (y - min_y) as usize, true);
+        }
+        canvas
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// Whether a pixel is lit; anything outside the canvas is unlit
+    pub fn get(&self, x: usize, y: usize) -> bool {
+        x < self.width && y < self.height && self.pixels[y * self.width + x]
+    }
+
+    pub fn set(&mut self, x: usize, y: usize, lit: bool) {
+        assert!(
+            x < self.width && y < self.height,
+            "({}, {}) is off the canvas",
+            x,
+            y
+        );
+        self.pixels[y * self.width + x] = lit;
+    }
+
+    pub fn rows(&self) -> impl Iterator<Item = &[bool]> {
+        // chunks panics on zero, and a zero-width canvas has no pixels anyway
+        self.pixels.chunks(self.width.max(1))
+    }
+
+    pub fn count_lit(&self) -> usize {
+        self.pixels.iter().filter(|&&lit| lit).count()
+    }
+
+    /// Columns and rows holding lit pixels, or `None` if nothing is lit
+    pub fn lit_bounds(&self) -> Option<(Range<usize>, Range<usize>)> {
+        let column_lit = |x| (0..self.height).any(|y| self.get(x, y));
+        let row_lit = |y| (0..self.width).any(|x| self.get(x, y));
+        let left = (0..self.width).find(|&x| column_lit(x))?;
+        let right = (0..self.width).rfind(|&x| column_lit(x))?;
+        let top = (0..self.height).find(|&y| row_lit(y))?;
+        let bottom = (0..self.height).rfind(|&y| row_lit(y))?;
+        Some((left..right + 1, top..bottom + 1))
+    }
+
+    /// Trim unlit borders, leaving an empty canvas if nothing is lit
+    pub fn crop(&self) -> Canvas {
+        let (xs, ys) = match self.lit_bounds() {
+            Some(bounds) => bounds,
+            None => return Canvas::default(),
+        };
+        let mut canvas = Canvas::new(xs.len(), ys.len());
+        for (y, src_y) in ys.enumerate() {
+            for (x, src_x) in xs.clone().enumerate() {
+                canvas.set(x, y, self.get(src_x, src_y));
+            }
+        }
+        canvas
+    }
+
+    /// Mirror top to bottom, turning y-up coordinates into screen ones
+    pub fn flip_vertical(&self) -> Canvas {
+        let rows = self.rows().collect::<Vec<_>>();
+        let mut canvas = self.clone();
+        canvas.pixels = rows.into_iter().rev().flatten().cloned().collect();
+        canvas
+    }
+
+    pub fn flip_horizontal(&self) -> Canvas {
+        let mut canvas = self.clone();
+        canvas.pixels = self
+            .rows()
+            .flat_map(|row| row.iter().rev().cloned())
+            .collect();
+        canvas
+    }
+
+    /// One character per pixel, each row ending in a newline
+    pub fn to_text(&self, lit: char, unlit: char) -> String {
+        let mut output = String::new();
+        for row in self.rows() {
+            output.extend(row.iter().map(|&on| if on { lit } else { unlit }));
+            output.push('\n');
+        }
+        output
+    }
+
+    /// Two rows per line using Unicode half blocks, for a squarer picture
+    pub fn to_half_blocks(&self) -> String {
+        let mut output = String::new();
+        for y in (0..self.height).step_by(2) {
+            output.extend(
+                (0..self.width).map(|x| match (self.get(x, y), self.get(x, y + 1)) {
+                    (true, true) => '█',
+                    (true, false) => '▀',
+                    (false, true) => '▄',
+                    (false, false) => ' ',
+                }),
+            );
+            output.push('\n');
+        }
+        output
+    }
+
+    /// Plain PBM, where lit pixels are drawn in black
+    pub fn to_pbm(&self) -> String {
+        let mut pbm = format!("P1\n{} {}\n", self.width, self.height);
+        for row in self.rows() {
+            let row = row
+                .iter()
+                .map(|&lit| if lit { "1" } else { "0" })
+                .collect::<Vec<_>>();
+            pbm.push_str(&row.join(" "));
+            pbm.push('\n');
+        }
+        pbm
+    }
+}
+
+#[test]
+fn test_canvas() {
+    let canvas = Canvas::from_points(vec![(-1, 5), (1, 6), (1, 4)]);
+    assert_eq!((canvas.width(), canvas.height()), (3, 3));
+    assert_eq!(canvas.to_text('#', '.'), "..#\n#..\n..#\n");
+    assert_eq!(
+        canvas.flip_horizontal().to_text('#', '.'),
+        "#..\n..#\n#..\n"
+    );
+    assert_eq!(canvas.to_half_blocks(), "▄ ▀\n  ▀\n");
+    assert_eq!(canvas.to_pbm(), "P1\n3 3\n0 0 1\n1 0 0\n0 0 1\n");
+    assert_eq!(canvas.count_lit(), 3);
+
+    let canvas = Canvas::from_rows(&[vec![false, false, false], vec![false, true], vec![true]]);
+    assert_eq!(canvas.lit_bounds(), Some((0..2, 1..3)));
+    assert_eq!(canvas.crop().to_text('#', '.'), ".#\n#.\n");
+    assert_eq!(canvas.flip_vertical().to_text('#', '.'), "#..\n.#.\n...\n");
+    assert!(!canvas.get(5, 5));
+
+    let empty = Canvas::from_points(Vec::new());
+    assert_eq!(empty, Canvas::default());
+    assert_eq!(empty.to_text('#', '.'), "");
+    assert_eq!(Canvas::new(4, 2).crop(), Canvas::default());
+}
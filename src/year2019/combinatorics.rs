@@ -0,0 +1,222 @@
+/// Every ordering of `items`, generated lazily with Heap's algorithm. Each
+/// step is a single swap, so only the yielded `Vec` is allocated.
+pub fn permutations<T: Clone>(items: &[T]) -> Permutations<T> {
+    Permutations {
+        items: items.to_vec(),
+        counters: vec![0; items.len()],
+        i: 0,
+    }
+}
+
+pub struct Permutations<T> {
+    items: Vec<T>,
+    counters: Vec<usize>,
+    /// Level of the next swap, or 0 before the first permutation is yielded
+    i: usize,
+}
+
+impl<T: Clone> Iterator for Permutations<T> {
+    type Item = Vec<T>;
+
+    fn next(&mut self) -> Option<Vec<T>> {
+        if self.i == 0 {
+            self.i = 1;
+            return Some(self.items.clone());
+        }
+        while self.i < self.items.len() {
+            let i = self.i;
+            if self.counters[i] < i {
+                let j = if i.is_multiple_of(2) {
+                    0
+                } else {
+                    self.counters[i]
+                };
+                self.items.swap(j, i);
+                self.counters[i] += 1;
+                self.i = 1;
+                return Some(self.items.clone());
+            }
+            self.counters[i] = 0;
+            self.i += 1;
+        }
+        None
+    }
+}
+
+/// Every choice of `k` items, keeping their order in `items`
+pub fn combinations<T: Clone>(items: &[T], k: usize) -> Combinations<T> {
+    Combinations {
+        items: items.to_vec(),
+        indices: (0..k).collect(),
+        started: false,
+    }
+}
+
+pub struct Combinations<T> {
+    items: Vec<T>,
+    indices: Vec<usize>,
+    started: bool,
+}
+
+impl<T: Clone> Iterator for Combinations<T> {
+    type Item = Vec<T>;
+
+    fn next(&mut self) -> Option<Vec<T>> {
+        let (n, k) = (self.items.len(), self.indices.len());
+        if k > n {
+            return None;
+        }
+        if self.started {
+            // advance the rightmost index that still has room
+            let i = (0..k).rev().find(|&i| self.indices[i] != i + n - k)?;
+            self.indices[i] += 1;
+            for j in i + 1..k {
+                self.indices[j] = self.indices[j - 1] + 1;
+            }
+        }
+        self.started = true;
+        Some(
+            self.indices
+                .iter()
+                .map(|&i| self.items[i].clone())
+                .collect(),
+        )
+    }
+}
+
+/// Every way of picking one item from each list, with the last list
+/// varying fastest
+pub fn product<T: Clone>(lists: &[&[T]]) -> Product<T> {
+    Product {
+        lists: lists.iter().map(|list| list.to_vec()).collect(),
+        indices: vec![0; lists.len()],
+        done: lists.iter().any(|list| list.is_empty()),
+    }
+}
+
+pub struct Product<T> {
+    lists: Vec<Vec<T>>,
+    indices: Vec<usize>,
+    done: bool,
+}
+
+impl<T: Clone> Iterator for Product<T> {
+    type Item = Vec<T>;
+
+    fn next(&mut self) -> Option<Vec<T>> {
+        if self.done {
+            return None;
+        }
+        let item = self
+            .indices
+            .iter()
+            .zip(&self.lists)
+            .map(|(&i, list)| list[i].clone())
+            .collect();
+        self.done = true;
+        for (i, list) in self.indices.iter_mut().zip(&self.lists).rev() {
+            *i += 1;
+            if *i < list.len() {
+                self.done = false;
+                break;
+            }
+            *i = 0;
+        }
+        Some(item)
+    }
+}
+
+/// Score every candidate across all cores, returning the best one with its
+/// score. Ties go to the last candidate, as with `Iterator::max_by_key`.
+pub fn par_argmax<C, K, F>(candidates: impl IntoIterator<Item = C>, score: F) -> Option<(C, K)>
+where
+    C: Sync,
+    K: Ord + Send,
+    F: Fn(&C) -> K + Sync,
+{
+    let candidates = candidates.into_iter().collect::<Vec<_>>();
+    let threads = std::thread::available_parallelism().map_or(1, |n| n.get());
+    let chunk = candidates.len().div_ceil(threads).max(1);
+    let score = &score;
+
+    let mut scores = std::thread::scope(|scope| {
+        let handles = candidates
+            .chunks(chunk)
+            .map(|chunk| scope.spawn(move || chunk.iter().map(score).collect::<Vec<_>>()))
+            .collect::<Vec<_>>();
+        handles
+            .into_iter()
+            .flat_map(|handle| handle.join().unwrap())
+            .collect::<Vec<_>>()
+    });
+
+    let mut best: Option<usize> = None;
+    for i in 0..scores.len() {
+        if best.is_none_or(|best| scores[i] >= scores[best]) {
+            best = Some(i);
+        }
+    }
+    let best = best?;
+    let candidate = candidates.into_iter().nth(best).unwrap();
+    Some((candidate, scores.swap_remove(best)))
+}
+
+#[test]
+fn test_permutations() {
+    let all = permutations(&[1, 2, 3, 4]).collect::<Vec<_>>();
+    assert_eq!(all.len(), 24);
+    assert_eq!(all[0], vec![1, 2, 3, 4]);
+    let mut sorted = all.clone();
+    sorted.sort();
+    sorted.dedup();
+    assert_eq!(sorted.len(), 24);
+
+    assert_eq!(permutations::<u8>(&[]).collect::<Vec<_>>(), vec![vec![]]);
+    assert_eq!(permutations(&['a']).count(), 1);
+    assert_eq!(permutations(&[0; 7]).count(), 5040);
+}
+
+#[test]
+fn test_combinations() {
+    assert_eq!(
+        combinations(&[1, 2, 3, 4], 2).collect::<Vec<_>>(),
+        vec![
+            vec![1, 2],
+            vec![1, 3],
+            vec![1, 4],
+            vec![2, 3],
+            vec![2, 4],
+            vec![3, 4]
+        ]
+    );
+    assert_eq!(
+        combinations(&[1, 2, 3], 0).collect::<Vec<_>>(),
+        vec![vec![]]
+    );
+    assert_eq!(combinations(&[1, 2, 3], 3).count(), 1);
+    assert_eq!(combinations(&[1, 2, 3], 4).count(), 0);
+    assert_eq!(combinations(&[0; 10], 4).count(), 210);
+}
+
+#[test]
+fn test_product() {
+    assert_eq!(
+        product(&[&[1, 2][..], &[3], &[4, 5]]).collect::<Vec<_>>(),
+        vec![vec![1, 3, 4], vec![1, 3, 5], vec![2, 3, 4], vec![2, 3, 5]]
+    );
+    assert_eq!(product::<u8>(&[]).collect::<Vec<_>>(), vec![vec![]]);
+    assert_eq!(product(&[&[1, 2][..], &[]]).count(), 0);
+}
+
+#[test]
+fn test_par_argmax() {
+    let best = par_argmax(permutations(&[3, 1, 2]), |p| p[0] * 100 + p[1] * 10 + p[2]);
+    assert_eq!(best, Some((vec![3, 2, 1], 321)));
+
+    // ties keep the last candidate
+    let best = par_argmax(0..1000, |&n| n / 100);
+    assert_eq!(best, Some((999, 9)));
+    assert_eq!(best.map(|(n, _)| n), (0..1000).max_by_key(|&n| n / 100));
+
+    assert_eq!(par_argmax(Vec::<u8>::new(), |&n| n), None);
+}
@@ -0,0 +1,127 @@
+use std::io::BufRead;
+
+/// Fuel needed to launch `mass`, ignoring the mass of the fuel itself
+pub fn calc_fuel(mass: u128) -> u128 {
+    (mass / 3).saturating_sub(2)
+}
+
+/// Successive amounts of fuel, each needed to carry the one before it.
+/// Every step divides by three, so even `u128::MAX` takes under 90 steps.
+pub struct FuelChain {
+    unaccounted: u128,
+}
+
+impl FuelChain {
+    pub fn new(mass: u128) -> Self {
+        Self { unaccounted: mass }
+    }
+}
+
+impl Iterator for FuelChain {
+    type Item = u128;
+
+    fn next(&mut self) -> Option<u128> {
+        self.unaccounted = calc_fuel(self.unaccounted);
+        Some(self.unaccounted).filter(|&fuel| fuel > 0)
+    }
+}
+
+/// Fuel for `mass` including fuel for the fuel. Always less than half of
+/// `mass`, so this cannot overflow.
+pub fn extrapolate_fuel(mass: u128) -> u128 {
+    FuelChain::new(mass).sum()
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Breakdown {
+    pub mass: u128,
+    pub base: u128,
+    /// Starts with `base`, followed by the fuel for each previous amount
+    pub chain: Vec<u128>,
+}
+
+impl Breakdown {
+    pub fn new(mass: u128) -> Self {
+        Self {
+            mass,
+            base: calc_fuel(mass),
+            chain: FuelChain::new(mass).collect(),
+        }
+    }
+
+    pub fn total(&self) -> u128 {
+        self.chain.iter().sum()
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Totals {
+    pub modules: usize,
+    pub base: u128,
+    pub total: u128,
+}
+
+impl Totals {
+    /// Sum fuel over every module in a single pass, or `None` on overflow
+    pub fn sum<I: IntoIterator<Item = u128>>(masses: I) -> Option<Totals> {
+        masses
+            .into_iter()
+            .try_fold(Totals::default(), |totals, mass| {
+                Some(Totals {
+                    modules: totals.modules + 1,
+                    base: totals.base.checked_add(calc_fuel(mass))?,
+                    total: totals.total.checked_add(extrapolate_fuel(mass))?,
+                })
+            })
+    }
+}
+
+/// Parse one mass per line without collecting them first
+pub fn read_masses<R: BufRead>(reader: R) -> impl Iterator<Item = std::io::Result<u128>> {
+    reader
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| line.as_ref().map_or(true, |line| !line.trim().is_empty()))
+        .map(|(i, line)| {
+            let line = line?;
+            line.trim().parse().map_err(|_| {
+                std::io::Error::new(
+                    std::io::ErrorKind::InvalidData,
+                    format!("line {}: invalid mass {:?}", i + 1, line),
+                )
+            })
+        })
+}
+
+#[test]
+fn test_breakdown() {
+    let breakdown = Breakdown::new(1969);
+    assert_eq!(breakdown.base, 654);
+    assert_eq!(breakdown.chain, vec![654, 216, 70, 21, 5]);
+    assert_eq!(breakdown.total(), 966);
+
+    assert!(extrapolate_fuel(u128::MAX) < u128::MAX / 2);
+    assert_eq!(Totals::sum(vec![u128::MAX; 3]), None);
+}
+
+#[test]
+fn test_read_masses() {
+    let input = "12\n14\n\n1969\n100756\n";
+    let totals = Totals::sum(read_masses(input.as_bytes()).map(Result::unwrap));
+    assert_eq!(
+        totals,
+        Some(Totals {
+            modules: 4,
+            base: 2 + 2 + 654 + 33583,
+            total: 2 + 2 + 966 + 50346,
+        })
+    );
+
+    let errors = read_masses("12\nheavy\n".as_bytes())
+        .map(|mass| mass.map_err(|e| e.to_string()))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        errors,
+        vec![Ok(12), Err("line 2: invalid mass \"heavy\"".to_owned())]
+    );
+}
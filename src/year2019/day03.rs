@@ -1,3 +1,5 @@
+use std::collections::HashMap;
+
 pub fn run() {
     let input = std::fs::read_to_string("inputs/year2019/day03.txt")
         .map(parse)
@@ -9,7 +11,7 @@ pub fn run() {
     println!("year2019 day03 part2 {}", answer2);
 }
 
-fn parse(input: impl AsRef<str>) -> Vec<Vec<Segment>> {
+pub fn parse(input: impl AsRef<str>) -> Vec<Vec<Segment>> {
     input
         .as_ref()
         .lines()
@@ -18,7 +20,7 @@ fn parse(input: impl AsRef<str>) -> Vec<Vec<Segment>> {
 }
 
 #[derive(Debug)]
-struct Segment {
+pub struct Segment {
     direction: (isize, isize),
     distance: usize,
 }
@@ -39,44 +41,349 @@ impl Segment {
     }
 }
 
-struct Intersection {
-    manhattan: isize,
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Intersection {
+    pub pos: Pos,
+    pub manhattan: isize,
+    /// Fewest combined steps for two different wires to reach `pos`
+    pub steps: isize,
+}
+
+pub type Pos = (isize, isize);
+
+/// A segment placed on the grid. Along its axis it covers the cells
+/// `lo..=hi`, which excludes `start` since the previous segment covers that.
+#[derive(Clone, Copy, Debug)]
+struct Line {
+    wire: usize,
+    /// y for horizontal lines, x for vertical ones
+    at: isize,
+    lo: isize,
+    hi: isize,
+    start: isize,
+    /// Steps taken along the wire before this segment
     steps: isize,
 }
 
-fn find_intersections(input: &Vec<Vec<Segment>>) -> Vec<Intersection> {
-    let mut map = std::collections::HashMap::new();
-    let mut intersections = Vec::new();
+impl Line {
+    fn steps_to(&self, c: isize) -> isize {
+        self.steps + (c - self.start).abs()
+    }
+}
 
-    for (index, path) in input.iter().enumerate() {
+/// Place every segment, split into horizontal and vertical lines
+fn place(input: &[Vec<Segment>]) -> (Vec<Line>, Vec<Line>) {
+    let mut horizontal = Vec::new();
+    let mut vertical = Vec::new();
+    for (wire, path) in input.iter().enumerate() {
         let mut pos = (0, 0);
         let mut steps = 0;
-        for Segment {
-            direction,
-            distance,
-        } in path
-        {
-            for _ in 0..*distance {
-                pos.0 += direction.0;
-                pos.1 += direction.1;
-                steps += 1;
-                let exists = map.insert(pos, (index, steps));
-                match exists {
-                    Some((other_index, other_steps)) if index != other_index => {
-                        intersections.push(Intersection {
-                            manhattan: pos.0.abs() + pos.1.abs(),
-                            steps: steps + other_steps,
-                        })
+        for segment in path {
+            let distance = segment.distance as isize;
+            let (dx, dy) = segment.direction;
+            let end = (pos.0 + dx * distance, pos.1 + dy * distance);
+            let (lines, at, start, stop, dir) = if dy == 0 {
+                (&mut horizontal, pos.1, pos.0, end.0, dx)
+            } else {
+                (&mut vertical, pos.0, pos.1, end.1, dy)
+            };
+            if distance > 0 {
+                lines.push(Line {
+                    wire,
+                    at,
+                    lo: (start + dir).min(stop),
+                    hi: (start + dir).max(stop),
+                    start,
+                    steps,
+                });
+            }
+            pos = end;
+            steps += distance;
+        }
+    }
+    (horizontal, vertical)
+}
+
+fn intersection(pos: Pos, steps: isize) -> Intersection {
+    Intersection {
+        pos,
+        manhattan: pos.0.abs() + pos.1.abs(),
+        steps,
+    }
+}
+
+/// Find every cell where lines of two different wires meet, using a sweep
+/// over x for crossing lines and a scan of each row or column for lines
+/// lying on top of each other. Results are sorted by position.
+pub fn find_intersections(input: &[Vec<Segment>]) -> Vec<Intersection> {
+    let (horizontal, vertical) = place(input);
+    let mut intersections = Vec::new();
+
+    // at equal x, add horizontal lines before checking verticals against them
+    let mut events = Vec::new();
+    for (i, line) in horizontal.iter().enumerate() {
+        events.push((line.lo, 0, i));
+        events.push((line.hi, 2, i));
+    }
+    for (i, line) in vertical.iter().enumerate() {
+        events.push((line.at, 1, i));
+    }
+    events.sort_unstable();
+
+    let mut active = std::collections::BTreeMap::<isize, Vec<usize>>::new();
+    for (_, kind, i) in events {
+        match kind {
+            0 => active.entry(horizontal[i].at).or_default().push(i),
+            1 => {
+                let v = &vertical[i];
+                for (&y, lines) in active.range(v.lo..=v.hi) {
+                    for h in lines.iter().map(|&j| &horizontal[j]) {
+                        if h.wire != v.wire {
+                            let steps = h.steps_to(v.at) + v.steps_to(y);
+                            intersections.push(intersection((v.at, y), steps));
+                        }
                     }
-                    _ => {}
                 }
             }
+            _ => {
+                let lines = active.get_mut(&horizontal[i].at).unwrap();
+                lines.retain(|&j| j != i);
+            }
         }
     }
 
+    for (mut lines, is_horizontal) in [(horizontal, true), (vertical, false)] {
+        lines.sort_unstable_by_key(|line| (line.at, line.lo));
+        for (i, a) in lines.iter().enumerate() {
+            for b in lines[i + 1..]
+                .iter()
+                .take_while(|b| b.at == a.at && b.lo <= a.hi)
+                .filter(|b| b.wire != a.wire)
+            {
+                for c in b.lo..=a.hi.min(b.hi) {
+                    let pos = if is_horizontal { (c, a.at) } else { (a.at, c) };
+                    intersections.push(intersection(pos, a.steps_to(c) + b.steps_to(c)));
+                }
+            }
+        }
+    }
+
+    // a wire crossing itself meets the others once per pass through a cell
+    intersections.retain(|i| i.pos != (0, 0));
+    intersections.sort_unstable_by_key(|i| (i.pos, i.steps));
+    intersections.dedup_by_key(|i| i.pos);
     intersections
 }
-fn part1(input: &Vec<Vec<Segment>>) -> isize {
+
+/// Every cell each wire passes through, with the steps it took to first get there
+pub struct Visits {
+    cells: HashMap<Pos, Vec<(usize, isize)>>,
+}
+
+impl Visits {
+    pub fn new(input: &[Vec<Segment>]) -> Visits {
+        let mut cells = HashMap::<_, Vec<_>>::new();
+        for (wire, path) in input.iter().enumerate() {
+            let mut pos = (0, 0);
+            let mut steps = 0;
+            for segment in path {
+                for _ in 0..segment.distance {
+                    pos.0 += segment.direction.0;
+                    pos.1 += segment.direction.1;
+                    steps += 1;
+                    let visits = cells.entry(pos).or_default();
+                    // wires are walked in order, so a revisit is always the last entry
+                    if visits.last().is_none_or(|&(last, _)| last != wire) {
+                        visits.push((wire, steps));
+                    }
+                }
+            }
+        }
+        Visits { cells }
+    }
+
+    /// Wires and their first-visit steps at `pos`, in wire order
+    pub fn get(&self, pos: Pos) -> &[(usize, isize)] {
+        self.cells.get(&pos).map_or(&[], Vec::as_slice)
+    }
+
+    /// Cells reached by at least `k` different wires
+    pub fn at_least(&self, k: usize) -> impl Iterator<Item = (Pos, &[(usize, isize)])> {
+        self.cells
+            .iter()
+            .filter(move |(_, visits)| visits.len() >= k)
+            .map(|(&pos, visits)| (pos, visits.as_slice()))
+    }
+
+    /// Same results as `find_intersections`, one per cell shared by two wires
+    pub fn intersections(&self) -> Vec<Intersection> {
+        let mut intersections = self
+            .at_least(2)
+            .filter(|&(pos, _)| pos != (0, 0))
+            .map(|(pos, visits)| {
+                let mut steps = visits.iter().map(|&(_, s)| s).collect::<Vec<_>>();
+                steps.sort_unstable();
+                intersection(pos, steps[0] + steps[1])
+            })
+            .collect::<Vec<_>>();
+        intersections.sort_unstable_by_key(|i| i.pos);
+        intersections
+    }
+}
+
+/// Corners of a wire's path, starting from the origin
+fn corners(path: &[Segment]) -> Vec<Pos> {
+    let mut pos = (0, 0);
+    let mut corners = vec![pos];
+    for segment in path {
+        let distance = segment.distance as isize;
+        pos.0 += segment.direction.0 * distance;
+        pos.1 += segment.direction.1 * distance;
+        corners.push(pos);
+    }
+    corners
+}
+
+/// The intersections closest to the origin by Manhattan distance and by steps
+fn closest(intersections: &[Intersection]) -> Option<(Intersection, Intersection)> {
+    let manhattan = intersections.iter().min_by_key(|i| i.manhattan)?;
+    let steps = intersections.iter().min_by_key(|i| i.steps)?;
+    Some((*manhattan, *steps))
+}
+
+static COLOURS: [&str; 6] = [
+    "#d62728", "#1f77b4", "#2ca02c", "#9467bd", "#ff7f0e", "#17becf",
+];
+
+/// Draw each wire in its own colour with y pointing up, marking the origin,
+/// every intersection, and the closest ones by distance and by steps
+pub fn render_svg(input: &[Vec<Segment>]) -> String {
+    use std::fmt::Write;
+
+    let paths = input.iter().map(|path| corners(path)).collect::<Vec<_>>();
+    let all = paths.iter().flatten();
+    let min_x = all.clone().map(|p| p.0).min().unwrap_or(0);
+    let max_x = all.clone().map(|p| p.0).max().unwrap_or(0);
+    let min_y = all.clone().map(|p| p.1).min().unwrap_or(0);
+    let max_y = all.map(|p| p.1).max().unwrap_or(0);
+    let size = (max_x - min_x).max(max_y - min_y).max(1);
+    let margin = size / 20 + 1;
+    let stroke = size as f64 / 500.0;
+
+    let mut svg = String::new();
+    writeln!(
+        svg,
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" viewBox=\"{} {} {} {}\">",
+        min_x - margin,
+        -max_y - margin,
+        max_x - min_x + 2 * margin,
+        max_y - min_y + 2 * margin
+    )
+    .unwrap();
+    for (wire, corners) in paths.iter().enumerate() {
+        let points = corners
+            .iter()
+            .map(|(x, y)| format!("{},{}", x, -y))
+            .collect::<Vec<_>>();
+        writeln!(
+            svg,
+            "  <polyline points=\"{}\" fill=\"none\" stroke=\"{}\" stroke-width=\"{}\"/>",
+            points.join(" "),
+            COLOURS[wire % COLOURS.len()],
+            stroke
+        )
+        .unwrap();
+    }
+
+    let intersections = find_intersections(input);
+    for i in &intersections {
+        writeln!(
+            svg,
+            "  <circle cx=\"{}\" cy=\"{}\" r=\"{}\" fill=\"gold\" stroke=\"black\" stroke-width=\"{}\"/>",
+            i.pos.0,
+            -i.pos.1,
+            stroke * 4.0,
+            stroke / 2.0
+        )
+        .unwrap();
+    }
+    writeln!(
+        svg,
+        "  <circle cx=\"0\" cy=\"0\" r=\"{}\" fill=\"black\"/>",
+        stroke * 6.0
+    )
+    .unwrap();
+
+    if let Some((manhattan, steps)) = closest(&intersections) {
+        let labels = [
+            (manhattan, format!("distance {}", manhattan.manhattan)),
+            (steps, format!("steps {}", steps.steps)),
+        ];
+        for (i, label) in labels.iter() {
+            writeln!(
+                svg,
+                "  <text x=\"{}\" y=\"{}\" font-size=\"{}\">{}</text>",
+                i.pos.0 as f64 + stroke * 6.0,
+                -i.pos.1 as f64 - stroke * 6.0,
+                stroke * 24.0,
+                label
+            )
+            .unwrap();
+        }
+    }
+    svg.push_str("</svg>\n");
+    svg
+}
+
+/// Draw the wires as text with y pointing up, or `None` if they span more
+/// than `max_size` cells in either direction. Each wire is drawn with its
+/// index, shared cells as `X`, the origin as `o`, and the closest
+/// intersections by distance and by steps as `M` and `S`.
+pub fn render_ascii(input: &[Vec<Segment>], max_size: usize) -> Option<String> {
+    let corners = input
+        .iter()
+        .flat_map(|path| corners(path))
+        .collect::<Vec<_>>();
+    let min_x = corners.iter().map(|p| p.0).min()?;
+    let max_x = corners.iter().map(|p| p.0).max()?;
+    let min_y = corners.iter().map(|p| p.1).min()?;
+    let max_y = corners.iter().map(|p| p.1).max()?;
+    if (max_x - min_x) as usize >= max_size || (max_y - min_y) as usize >= max_size {
+        return None;
+    }
+
+    let visits = Visits::new(input);
+    let intersections = visits.intersections();
+    let closest = closest(&intersections);
+
+    let mut output = String::new();
+    for y in (min_y..=max_y).rev() {
+        let mut line = String::new();
+        for x in min_x..=max_x {
+            let pos = (x, y);
+            line.push(match (pos, closest, visits.get(pos)) {
+                ((0, 0), _, _) => 'o',
+                (_, Some((m, s)), _) if pos == m.pos && pos == s.pos => '*',
+                (_, Some((m, _)), _) if pos == m.pos => 'M',
+                (_, Some((_, s)), _) if pos == s.pos => 'S',
+                (_, _, []) => ' ',
+                (_, _, [(wire, _)]) => std::char::from_digit(*wire as u32 % 36, 36).unwrap(),
+                _ => 'X',
+            });
+        }
+        output.push_str(line.trim_end());
+        output.push('\n');
+    }
+    if let Some((manhattan, steps)) = closest {
+        output.push_str(&format!(
+            "M {:?} distance {}\nS {:?} steps {}\n",
+            manhattan.pos, manhattan.manhattan, steps.pos, steps.steps
+        ));
+    }
+    Some(output)
+}
+
+fn part1(input: &[Vec<Segment>]) -> isize {
     find_intersections(input)
         .iter()
         .map(|i| i.manhattan)
@@ -84,7 +391,7 @@ fn part1(input: &Vec<Vec<Segment>>) -> isize {
         .unwrap()
 }
 
-fn part2(input: &Vec<Vec<Segment>>) -> isize {
+fn part2(input: &[Vec<Segment>]) -> isize {
     find_intersections(input)
         .iter()
         .map(|i| i.steps)
@@ -129,3 +436,115 @@ fn test_part2() {
         410,
     );
 }
+
+#[cfg(test)]
+fn random_wires(seed: u64, wires: usize, segments: usize, max: usize) -> Vec<Vec<Segment>> {
+    let mut rng = crate::year2019::testing::Rng::new(seed);
+    (0..wires)
+        .map(|_| {
+            (0..segments)
+                .map(|_| Segment {
+                    direction: [(-1, 0), (1, 0), (0, 1), (0, -1)][rng.below(4)],
+                    distance: 1 + rng.below(max),
+                })
+                .collect()
+        })
+        .collect()
+}
+
+#[test]
+fn test_find_intersections() {
+    for seed in 0..20 {
+        let wires = random_wires(seed, 2 + seed as usize % 3, 40, 12);
+        assert_eq!(
+            find_intersections(&wires),
+            Visits::new(&wires).intersections()
+        );
+    }
+
+    // a third wire crossing both of the others, and running along one of them
+    let wires = parse("R8,U5,L5,D3\nU7,R6,D4,L4\nU4,R10");
+    let mut found = find_intersections(&wires)
+        .iter()
+        .map(|i| (i.pos, i.steps))
+        .collect::<Vec<_>>();
+    found.sort_unstable();
+    assert_eq!(
+        found,
+        vec![
+            ((0, 1), 2),
+            ((0, 2), 4),
+            ((0, 3), 6),
+            ((0, 4), 8),
+            ((3, 3), 40),
+            ((3, 4), 26),
+            ((6, 4), 26),
+            ((6, 5), 30),
+            ((8, 4), 24)
+        ]
+    );
+}
+
+#[test]
+#[ignore]
+fn bench_find_intersections() {
+    let wires = random_wires(7, 2, 400, 2000);
+    let time = |f: fn(&[Vec<Segment>]) -> Vec<Intersection>| {
+        let start = std::time::Instant::now();
+        let found = f(&wires).len();
+        (start.elapsed(), found)
+    };
+    let (sweep, found) = time(find_intersections);
+    let (cells, _) = time(|wires| Visits::new(wires).intersections());
+    println!(
+        "{} intersections: sweep {:?}, cells {:?}",
+        found, sweep, cells
+    );
+}
+
+#[test]
+fn test_self_crossing() {
+    // the first wire passes (2, 0) at steps 2 and 10; only the first counts
+    let wires = parse("R4,U2,L2,D4\nU1,R2,D1");
+    assert_eq!(Visits::new(&wires).get((2, 0)), &[(0, 2), (1, 4)]);
+    assert_eq!(part2(&wires), 6);
+    assert_eq!(
+        find_intersections(&wires),
+        Visits::new(&wires).intersections()
+    );
+
+    let wires = parse("R8,U5,L5,D3\nU7,R6,D4,L4\nU4,R10");
+    let visits = Visits::new(&wires);
+    assert_eq!(visits.at_least(3).count(), 0);
+    let mut shared = visits.at_least(2).map(|(pos, _)| pos).collect::<Vec<_>>();
+    shared.sort_unstable();
+    assert_eq!(shared[..4], [(0, 1), (0, 2), (0, 3), (0, 4)]);
+    assert_eq!(shared.len(), 9);
+}
+
+#[test]
+fn test_render() {
+    let wires = parse("R8,U5,L5,D3\nU7,R6,D4,L4");
+    assert_eq!(render_ascii(&wires, 8), None);
+    assert_eq!(
+        render_ascii(&wires, 20).unwrap(),
+        "1111111\n\
+         1     1\n\
+         1  000S00\n\
+         1  0  1 0\n\
+         1 1M111 0\n\
+         1  0    0\n\
+         1       0\n\
+         o00000000\n\
+         M (3, 3) distance 6\n\
+         S (6, 5) steps 30\n"
+    );
+
+    let svg = render_svg(&wires);
+    assert!(svg.starts_with("<svg"));
+    assert_eq!(svg.matches("<polyline").count(), 2);
+    assert!(svg.contains("points=\"0,0 8,0 8,-5 3,-5 3,-2\""));
+    assert_eq!(svg.matches("fill=\"gold\"").count(), 2);
+    assert!(svg.contains(">distance 6</text>"));
+    assert!(svg.contains(">steps 30</text>"));
+}
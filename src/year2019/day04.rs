@@ -1,94 +1,452 @@
+use std::collections::HashMap;
+use std::ops::RangeInclusive;
+
 pub fn run() {
     let input: Vec<Code> = std::fs::read_to_string("inputs/year2019/day04.txt")
         .unwrap()
         .trim()
-        .split("-")
+        .split('-')
         .map(Code::from_str)
         .collect::<Vec<_>>();
-    let a = input[0];
-    let b = input[1];
+    let a = &input[0];
+    let b = &input[1];
     println!("year2019 day04 part1 {}", part1(a, b));
     println!("year2019 day04 part2 {}", part2(a, b));
 }
 
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
 pub struct Code {
-    digits: [u8; 6],
+    digits: Vec<u8>,
 }
 
 impl Code {
+    /// Parse a code as wide as the string, keeping any leading zeros
     fn from_str(s: &str) -> Code {
-        assert!(s.parse::<usize>().is_ok());
-        let mut i = 6;
-        let mut code = Code { digits: [0; 6] };
-        s.chars()
-            .rev()
-            .map(|d| {
-                i -= 1;
-                code.digits[i] = (d as u8) - ('0' as u8);
-            })
-            .count();
-        while i > 0 {
-            i -= 1;
-            code.digits[i] = 0;
+        assert!(s.chars().all(|c| c.is_ascii_digit()));
+        Code {
+            digits: s.bytes().map(|d| d - b'0').collect(),
         }
-        code
     }
 
+    pub fn new(digits: Vec<u8>) -> Code {
+        assert!(digits.iter().all(|&d| d < 10));
+        Code { digits }
+    }
+
+    pub fn digits(&self) -> &[u8] {
+        &self.digits
+    }
+
+    /// The same number with leading zeros added to make it `width` digits wide
+    fn padded(&self, width: usize) -> Code {
+        let mut digits = vec![0; width.saturating_sub(self.digits.len())];
+        digits.extend(&self.digits);
+        Code { digits }
+    }
+
+    #[cfg(test)]
     fn is_valid_part1(&self) -> bool {
         self.digits.windows(2).all(|two| two[0] <= two[1])
             && self.digits.windows(2).any(|two| two[0] == two[1])
     }
 
+    #[cfg(test)]
     fn is_valid_part2(&self, storage: &mut Vec<u8>) -> bool {
         storage.clear();
         storage.push(1);
         let mut last = self.digits[0];
-        let mut i = 1;
-        while i < 6 {
-            if self.digits[i] == last {
+        for &digit in &self.digits[1..] {
+            if digit == last {
                 *storage.last_mut().unwrap() += 1;
             } else {
-                last = self.digits[i];
+                last = digit;
                 storage.push(1);
             }
-            i += 1;
         }
-        storage.iter().any(|x| *x == 2)
+        storage.contains(&2)
     }
 
-    fn increment(&mut self) {
-        let mut i = 5;
-        loop {
-            self.digits[i] += 1;
-            if self.digits[i] < 10 {
+    /// Step to the next code of the same width, returning false on wrapping to zero
+    pub fn increment(&mut self) -> bool {
+        for digit in self.digits.iter_mut().rev() {
+            *digit += 1;
+            if *digit < 10 {
+                return true;
+            }
+            *digit = 0;
+        }
+        false
+    }
+
+    /// Raise every digit after the first decrease to match the one before it,
+    /// giving the smallest non-decreasing code not below this one
+    fn make_non_decreasing(&mut self) {
+        if let Some(i) = self.digits.windows(2).position(|two| two[0] > two[1]) {
+            let digit = self.digits[i];
+            self.digits[i + 1..].iter_mut().for_each(|d| *d = digit);
+        }
+    }
+}
+
+/// Which repeated digits a code needs on top of never decreasing
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Pairs {
+    /// Two adjacent digits are the same
+    AtLeastTwo,
+    /// Some run of repeated digits is exactly two long
+    ExactlyTwo,
+}
+
+impl Pairs {
+    fn accepts(self, run: u8) -> bool {
+        match self {
+            Pairs::AtLeastTwo => run >= 2,
+            Pairs::ExactlyTwo => run == 2,
+        }
+    }
+}
+
+/// Digits chosen so far: the last digit, how long its run is (3 meaning
+/// "longer than two") and whether an earlier run already satisfied the rule
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq)]
+struct State {
+    last: u8,
+    run: u8,
+    satisfied: bool,
+}
+
+impl State {
+    fn push(state: Option<State>, digit: u8, rule: Pairs) -> State {
+        match state {
+            Some(s) if s.last == digit => State {
+                run: (s.run + 1).min(3),
+                ..s
+            },
+            Some(s) => State {
+                last: digit,
+                run: 1,
+                satisfied: s.satisfied || rule.accepts(s.run),
+            },
+            None => State {
+                last: digit,
+                run: 1,
+                satisfied: false,
+            },
+        }
+    }
+
+    fn valid(self, rule: Pairs) -> bool {
+        self.satisfied || rule.accepts(self.run)
+    }
+}
+
+/// Both bounds at the width of the wider one, so `1..100` covers `001..100`
+fn same_width(lo: &Code, hi: &Code) -> (Code, Code) {
+    let width = lo.digits.len().max(hi.digits.len());
+    (lo.padded(width), hi.padded(width))
+}
+
+/// Counts valid codes by digit DP, only ever extending non-decreasing prefixes.
+/// There are more than `u64::MAX` such codes from about 700 digits wide, but
+/// every count fits in a `u128` up to 79257 digits.
+struct Counter {
+    rule: Pairs,
+    memo: HashMap<(usize, State), u128>,
+}
+
+impl Counter {
+    /// Valid ways to append `remaining` more digits after `state`
+    fn completions(&mut self, remaining: usize, state: State) -> u128 {
+        if remaining == 0 {
+            return state.valid(self.rule) as u128;
+        }
+        if let Some(&count) = self.memo.get(&(remaining, state)) {
+            return count;
+        }
+        let count = (state.last..10)
+            .map(|digit| {
+                let next = State::push(Some(state), digit, self.rule);
+                self.completions(remaining - 1, next)
+            })
+            .sum();
+        self.memo.insert((remaining, state), count);
+        count
+    }
+
+    /// Valid codes of the same width that sort below `bound`
+    fn below(&mut self, bound: &[u8]) -> u128 {
+        let mut total = 0;
+        let mut state = None;
+        for (i, &limit) in bound.iter().enumerate() {
+            let min = state.map_or(0, |s: State| s.last);
+            for digit in min..limit {
+                let next = State::push(state, digit, self.rule);
+                total += self.completions(bound.len() - i - 1, next);
+            }
+            if limit < min {
+                // nothing sharing this prefix can be non-decreasing
                 break;
             }
-            self.digits[i] = 0;
-            i -= 1;
+            state = Some(State::push(state, limit, self.rule));
         }
+        total
     }
 }
 
-fn part1(mut a: Code, b: Code) -> usize {
-    let mut valid_codes = 0;
-    while a.digits < b.digits {
-        if a.is_valid_part1() {
-            valid_codes += 1;
+/// Count non-decreasing codes in `lo..hi` meeting `rule`, for codes of any width.
+/// The narrower bound is padded with leading zeros.
+pub fn count(lo: &Code, hi: &Code, rule: Pairs) -> u128 {
+    let (lo, hi) = same_width(lo, hi);
+    let mut counter = Counter {
+        rule,
+        memo: HashMap::new(),
+    };
+    counter
+        .below(&hi.digits)
+        .saturating_sub(counter.below(&lo.digits))
+}
+
+/// Constraint on the digits of a code, combinable into larger rule sets
+#[derive(Clone, Debug, PartialEq)]
+pub enum Rule {
+    /// Digits never decrease from left to right
+    NonDecreasing,
+    /// Some run of one repeated digit has a length in this range
+    Run(RangeInclusive<usize>),
+    /// The digits add up to a total in this range
+    DigitSum(RangeInclusive<u32>),
+    /// None of these digits appear
+    Forbidden(Vec<u8>),
+    All(Vec<Rule>),
+    Any(Vec<Rule>),
+    Not(Box<Rule>),
+}
+
+impl Rule {
+    /// The original rules, which `count` handles much faster
+    pub fn for_pairs(pairs: Pairs) -> Rule {
+        let run = match pairs {
+            Pairs::AtLeastTwo => 2..=usize::MAX,
+            Pairs::ExactlyTwo => 2..=2,
+        };
+        Rule::All(vec![Rule::NonDecreasing, Rule::Run(run)])
+    }
+
+    pub fn matches(&self, digits: &[u8]) -> bool {
+        match self {
+            Rule::NonDecreasing => digits.windows(2).all(|two| two[0] <= two[1]),
+            Rule::Run(lengths) => {
+                let mut start = 0;
+                (1..=digits.len()).any(|i| {
+                    if i < digits.len() && digits[i] == digits[start] {
+                        return false;
+                    }
+                    let length = i - start;
+                    start = i;
+                    lengths.contains(&length)
+                })
+            }
+            Rule::DigitSum(sums) => sums.contains(&digits.iter().map(|&d| d as u32).sum()),
+            Rule::Forbidden(forbidden) => !digits.iter().any(|d| forbidden.contains(d)),
+            Rule::All(rules) => rules.iter().all(|rule| rule.matches(digits)),
+            Rule::Any(rules) => rules.iter().any(|rule| rule.matches(digits)),
+            Rule::Not(rule) => !rule.matches(digits),
+        }
+    }
+
+    /// Whether every match is non-decreasing, so other codes can be skipped
+    fn non_decreasing(&self) -> bool {
+        match self {
+            Rule::NonDecreasing => true,
+            Rule::All(rules) => rules.iter().any(Rule::non_decreasing),
+            _ => false,
+        }
+    }
+
+    /// Lazily list the matching codes in `lo..hi`, padding the narrower bound
+    /// with leading zeros
+    pub fn matching(&self, lo: &Code, hi: &Code) -> Matches<'_> {
+        let (lo, hi) = same_width(lo, hi);
+        Matches {
+            rule: self,
+            skip: self.non_decreasing(),
+            next: Some(lo),
+            hi,
+        }
+    }
+
+    pub fn count(&self, lo: &Code, hi: &Code) -> u128 {
+        self.matching(lo, hi).count() as u128
+    }
+}
+
+pub struct Matches<'a> {
+    rule: &'a Rule,
+    skip: bool,
+    next: Option<Code>,
+    hi: Code,
+}
+
+impl Iterator for Matches<'_> {
+    type Item = Code;
+
+    fn next(&mut self) -> Option<Code> {
+        loop {
+            let mut code = self.next.take()?;
+            if self.skip {
+                code.make_non_decreasing();
+            }
+            if code >= self.hi {
+                return None;
+            }
+            let mut following = code.clone();
+            if following.increment() {
+                self.next = Some(following);
+            }
+            if self.rule.matches(&code.digits) {
+                return Some(code);
+            }
         }
-        a.increment();
     }
-    valid_codes
 }
 
-fn part2(mut a: Code, b: Code) -> usize {
-    let mut valid_codes = 0;
-    let mut storage = Vec::with_capacity(6);
-    while a.digits < b.digits {
-        if a.is_valid_part1() && a.is_valid_part2(&mut storage) {
-            valid_codes += 1;
+fn part1(a: &Code, b: &Code) -> u128 {
+    count(a, b, Pairs::AtLeastTwo)
+}
+
+fn part2(a: &Code, b: &Code) -> u128 {
+    count(a, b, Pairs::ExactlyTwo)
+}
+
+#[test]
+fn test_is_valid() {
+    let mut storage = Vec::new();
+    assert!(Code::from_str("111111").is_valid_part1());
+    assert!(!Code::from_str("223450").is_valid_part1());
+    assert!(!Code::from_str("123789").is_valid_part1());
+    assert!(Code::from_str("112233").is_valid_part2(&mut storage));
+    assert!(!Code::from_str("123444").is_valid_part2(&mut storage));
+    assert!(Code::from_str("111122").is_valid_part2(&mut storage));
+}
+
+#[test]
+fn test_count() {
+    fn brute_force(mut a: Code, b: &Code) -> (u128, u128) {
+        let mut storage = Vec::new();
+        let mut valid = (0, 0);
+        while &a < b {
+            if a.is_valid_part1() {
+                valid.0 += 1;
+                if a.is_valid_part2(&mut storage) {
+                    valid.1 += 1;
+                }
+            }
+            a.increment();
         }
-        a.increment();
+        valid
+    }
+
+    let ranges = [
+        ("00", "99"),
+        ("100", "999"),
+        ("0000", "9999"),
+        ("11111", "11112"),
+        ("12345", "54321"),
+        ("99999", "99999"),
+        ("138241", "674034"),
+        ("234208", "765869"),
+        ("3000000", "3999999"),
+    ];
+    for &(a, b) in &ranges {
+        let (a, b) = (Code::from_str(a), Code::from_str(b));
+        let counted = (
+            count(&a, &b, Pairs::AtLeastTwo),
+            count(&a, &b, Pairs::ExactlyTwo),
+        );
+        assert_eq!(counted, brute_force(a, &b));
+    }
+
+    // far too many codes to walk one by one
+    let (a, b) = (
+        Code::from_str(&"0".repeat(30)),
+        Code::from_str(&"9".repeat(30)),
+    );
+    assert!(count(&a, &b, Pairs::ExactlyTwo) < count(&a, &b, Pairs::AtLeastTwo));
+
+    // bounds of different widths count at the wider one
+    let (a, b) = (Code::from_str("1"), Code::from_str("100"));
+    let padded = brute_force(Code::from_str("001"), &b);
+    assert_eq!(count(&a, &b, Pairs::AtLeastTwo), padded.0);
+    assert_eq!(count(&a, &b, Pairs::ExactlyTwo), padded.1);
+    assert_eq!(count(&b, &a, Pairs::AtLeastTwo), 0);
+
+    // every non-decreasing code has a pair once it's wider than 10 digits,
+    // so this is all C(709, 9) of them bar the upper bound itself
+    let (a, b) = (
+        Code::from_str(&"0".repeat(700)),
+        Code::from_str(&"9".repeat(700)),
+    );
+    let choose = (1..=9).fold(1u128, |c, i| c * (700 + i) / i);
+    assert!(choose > u64::MAX as u128);
+    assert_eq!(count(&a, &b, Pairs::AtLeastTwo), choose - 1);
+}
+
+#[test]
+fn test_rules() {
+    let (a, b) = (Code::from_str("138241"), Code::from_str("674034"));
+    for &pairs in &[Pairs::AtLeastTwo, Pairs::ExactlyTwo] {
+        let rule = Rule::for_pairs(pairs);
+        assert_eq!(rule.count(&a, &b), count(&a, &b, pairs));
     }
-    valid_codes
+
+    // the narrower bound is padded, so 1 counts as 001
+    let (one, hundred) = (Code::from_str("1"), Code::from_str("100"));
+    let rule = Rule::for_pairs(Pairs::AtLeastTwo);
+    let first = rule.matching(&one, &hundred).next().unwrap();
+    assert_eq!(first.digits, vec![0, 0, 1]);
+    assert_eq!(
+        rule.count(&one, &hundred),
+        count(&one, &hundred, Pairs::AtLeastTwo)
+    );
+
+    let found = Rule::for_pairs(Pairs::ExactlyTwo)
+        .matching(&a, &b)
+        .take(3)
+        .map(|code| code.digits)
+        .collect::<Vec<_>>();
+    assert_eq!(
+        found,
+        vec![
+            vec![1, 3, 8, 8, 9, 9],
+            vec![1, 4, 4, 4, 5, 5],
+            vec![1, 4, 4, 4, 6, 6]
+        ]
+    );
+
+    // no digits above 6 that sum to 20, with a run of three or more
+    let rule = Rule::All(vec![
+        Rule::Forbidden(vec![7, 8, 9]),
+        Rule::DigitSum(20..=20),
+        Rule::Run(3..=4),
+    ]);
+    let (a, b) = (Code::from_str("0000"), Code::from_str("9999"));
+    let expected = (0..10000)
+        .map(|n| format!("{:04}", n))
+        .map(|s| Code::from_str(&s))
+        .filter(|code| {
+            let digits = &code.digits;
+            digits.iter().all(|&d| d <= 6)
+                && digits.iter().map(|&d| d as u32).sum::<u32>() == 20
+                && digits.windows(3).any(|w| w[0] == w[1] && w[1] == w[2])
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(rule.matching(&a, &b).collect::<Vec<_>>(), expected);
+
+    let rule = Rule::Any(vec![
+        Rule::Not(Box::new(Rule::NonDecreasing)),
+        Rule::Run(1..=1),
+    ]);
+    assert!(rule.matches(&[2, 1]));
+    assert!(rule.matches(&[1, 2, 2]));
+    assert!(!rule.matches(&[3, 3, 3]));
 }
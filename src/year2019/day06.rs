@@ -1,67 +1,556 @@
-use std::collections::{HashMap, HashSet};
+use std::collections::HashMap;
 
 pub fn run() {
     let input = std::fs::read_to_string("inputs/year2019/day06.txt").unwrap();
-    let pairs: Vec<[&str; 2]> = input
-        .lines()
-        .map(|line| {
-            let mut parts = line.split(")");
-            [parts.next().unwrap(), parts.next().unwrap()]
-        })
-        .collect();
-    let orbits = pairs
-        .iter()
-        .map(|&[center, satellite]| (satellite, center))
-        .collect::<HashMap<_, _>>();
-    println!("year2019 day06 part1 {}", part1(&pairs, &orbits));
+    let orbits = OrbitMap::parse(&input).unwrap();
+    println!("year2019 day06 part1 {}", part1(&orbits));
     println!("year2019 day06 part2 {}", part2(&orbits));
 }
 
-fn distance<'a>(
-    satellite: &'a str,
-    orbits: &HashMap<&'a str, &'a str>,
-    distances: &mut HashMap<&'a str, usize>,
-) -> usize {
-    if let Some(d) = distances.get(satellite) {
-        *d
-    } else {
-        let d = distance(orbits[satellite], orbits, distances) + 1;
-        distances.insert(satellite, d);
-        d
+#[derive(Clone, Debug, PartialEq)]
+pub enum OrbitError {
+    /// A line that isn't `CENTER)SATELLITE`
+    Syntax {
+        line: usize,
+        text: String,
+    },
+    /// An object listed as orbiting two different centers
+    TwoCenters {
+        object: String,
+        first: String,
+        second: String,
+    },
+    /// No objects at all
+    NoRoot,
+    /// More than one object orbits nothing
+    MultipleRoots(Vec<String>),
+    UnknownObject(String),
+    /// Objects that never reach the root because they belong to another tree
+    Orphans(Vec<String>),
+    /// Objects orbiting each other in a loop, in orbit order
+    Cycle(Vec<String>),
+}
+
+impl std::fmt::Display for OrbitError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            OrbitError::Syntax { line, text } => {
+                write!(
+                    f,
+                    "line {}: expected CENTER)SATELLITE, got {:?}",
+                    line, text
+                )
+            }
+            OrbitError::TwoCenters {
+                object,
+                first,
+                second,
+            } => write!(f, "{} orbits both {} and {}", object, first, second),
+            OrbitError::NoRoot => write!(f, "no objects"),
+            OrbitError::MultipleRoots(roots) => write!(f, "multiple roots: {}", roots.join(", ")),
+            OrbitError::UnknownObject(name) => write!(f, "unknown object {}", name),
+            OrbitError::Orphans(names) => write!(f, "not orbiting the root: {}", names.join(", ")),
+            OrbitError::Cycle(names) => write!(f, "orbits form a cycle: {}", names.join(")")),
+        }
     }
 }
 
-fn part1(pairs: &[[&str; 2]], orbits: &HashMap<&str, &str>) -> usize {
-    let mut distances = HashMap::new();
-    distances.insert("COM", 0);
-    pairs
-        .iter()
-        .flatten()
-        .collect::<HashSet<_>>()
-        .iter()
-        .map(|object| distance(object, &orbits, &mut distances))
-        .sum()
+impl std::error::Error for OrbitError {}
+
+/// Interned object, indexing into an `OrbitMap`
+pub type Id = usize;
+
+/// Tree of objects, each orbiting its parent, with depths and subtree sizes
+/// computed up front
+#[derive(Clone, Debug)]
+pub struct OrbitMap {
+    names: Vec<String>,
+    ids: HashMap<String, Id>,
+    parents: Vec<Option<Id>>,
+    children: Vec<Vec<Id>>,
+    depths: Vec<usize>,
+    sizes: Vec<usize>,
+    root: Id,
 }
 
-fn path<'a>(satellite: &'a str, orbits: &HashMap<&'a str, &'a str>) -> Vec<&'a str> {
-    if satellite == "COM" {
-        return vec!["COM"];
+impl OrbitMap {
+    /// Parse `CENTER)SATELLITE` lines, requiring exactly one object that
+    /// orbits nothing
+    pub fn parse(input: &str) -> Result<OrbitMap, OrbitError> {
+        let mut map = OrbitMap::parse_unrooted(input)?;
+        let roots = (0..map.len())
+            .filter(|&id| map.parents[id].is_none())
+            .collect::<Vec<_>>();
+        match roots[..] {
+            [root] => {
+                map.root = root;
+                map.finish()
+            }
+            [] if map.names.is_empty() => Err(OrbitError::NoRoot),
+            // with no roots, following centers from anywhere must loop
+            [] => Err(OrbitError::Cycle(map.find_cycle(&[0]).unwrap())),
+            _ => Err(OrbitError::MultipleRoots(
+                roots.iter().map(|&id| map.names[id].clone()).collect(),
+            )),
+        }
+    }
+
+    /// Parse with a chosen root, rejecting objects that don't orbit it
+    pub fn parse_rooted(input: &str, root: &str) -> Result<OrbitMap, OrbitError> {
+        let mut map = OrbitMap::parse_unrooted(input)?;
+        map.root = map.lookup(root)?;
+        map.finish()
+    }
+
+    fn parse_unrooted(input: &str) -> Result<OrbitMap, OrbitError> {
+        let mut map = OrbitMap {
+            names: Vec::new(),
+            ids: HashMap::new(),
+            parents: Vec::new(),
+            children: Vec::new(),
+            depths: Vec::new(),
+            sizes: Vec::new(),
+            root: 0,
+        };
+        for (i, line) in input.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() {
+                continue;
+            }
+            let mut parts = line.split(')');
+            let (center, satellite) = match (parts.next(), parts.next(), parts.next()) {
+                (Some(center), Some(satellite), None)
+                    if !center.is_empty() && !satellite.is_empty() =>
+                {
+                    (map.intern(center), map.intern(satellite))
+                }
+                _ => {
+                    return Err(OrbitError::Syntax {
+                        line: i + 1,
+                        text: line.to_owned(),
+                    })
+                }
+            };
+            match map.parents[satellite] {
+                Some(existing) if existing != center => {
+                    return Err(OrbitError::TwoCenters {
+                        object: map.names[satellite].clone(),
+                        first: map.names[existing].clone(),
+                        second: map.names[center].clone(),
+                    })
+                }
+                Some(_) => {}
+                None => {
+                    map.parents[satellite] = Some(center);
+                    map.children[center].push(satellite);
+                }
+            }
+        }
+        Ok(map)
+    }
+
+    fn intern(&mut self, name: &str) -> Id {
+        if let Some(&id) = self.ids.get(name) {
+            return id;
+        }
+        let id = self.names.len();
+        self.names.push(name.to_owned());
+        self.ids.insert(name.to_owned(), id);
+        self.parents.push(None);
+        self.children.push(Vec::new());
+        id
+    }
+
+    /// Walk outwards from the root to find depths and subtree sizes
+    fn finish(mut self) -> Result<OrbitMap, OrbitError> {
+        let mut order = vec![self.root];
+        self.depths = vec![usize::MAX; self.len()];
+        self.depths[self.root] = 0;
+        let mut i = 0;
+        while i < order.len() {
+            let id = order[i];
+            for &child in &self.children[id] {
+                self.depths[child] = self.depths[id] + 1;
+                order.push(child);
+            }
+            i += 1;
+        }
+
+        if order.len() < self.len() {
+            let unreached = (0..self.len())
+                .filter(|&id| self.depths[id] == usize::MAX)
+                .collect::<Vec<_>>();
+            if let Some(cycle) = self.find_cycle(&unreached) {
+                return Err(OrbitError::Cycle(cycle));
+            }
+            let mut names = unreached
+                .iter()
+                .map(|&id| self.names[id].clone())
+                .collect::<Vec<_>>();
+            names.sort();
+            return Err(OrbitError::Orphans(names));
+        }
+
+        self.sizes = vec![1; self.len()];
+        for &id in order.iter().rev() {
+            if let Some(parent) = self.parents[id] {
+                self.sizes[parent] += self.sizes[id];
+            }
+        }
+        Ok(self)
+    }
+
+    /// Follow centers from each start in turn, returning the first loop
+    /// found. Walks stop early at objects an earlier walk already covered,
+    /// so this is linear overall.
+    fn find_cycle(&self, starts: &[Id]) -> Option<Vec<String>> {
+        // the walk that first reached each object, and at which step
+        let mut seen = vec![None; self.len()];
+        for (walk, &start) in starts.iter().enumerate() {
+            let mut path: Vec<Id> = Vec::new();
+            let mut current = Some(start);
+            while let Some(id) = current {
+                match seen[id] {
+                    Some((w, index)) if w == walk => {
+                        let mut cycle = path[index..]
+                            .iter()
+                            .rev()
+                            .map(|&id| self.names[id].clone())
+                            .collect::<Vec<String>>();
+                        // close the loop so it reads as a chain of orbits
+                        cycle.push(cycle[0].clone());
+                        return Some(cycle);
+                    }
+                    Some(_) => break,
+                    None => {}
+                }
+                seen[id] = Some((walk, path.len()));
+                path.push(id);
+                current = self.parents[id];
+            }
+        }
+        None
+    }
+
+    pub fn len(&self) -> usize {
+        self.names.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.names.is_empty()
+    }
+
+    pub fn id(&self, name: &str) -> Option<Id> {
+        self.ids.get(name).cloned()
+    }
+
+    pub fn lookup(&self, name: &str) -> Result<Id, OrbitError> {
+        self.id(name)
+            .ok_or_else(|| OrbitError::UnknownObject(name.to_owned()))
+    }
+
+    pub fn name(&self, id: Id) -> &str {
+        &self.names[id]
+    }
+
+    pub fn root(&self) -> Id {
+        self.root
+    }
+
+    pub fn parent(&self, id: Id) -> Option<Id> {
+        self.parents[id]
+    }
+
+    pub fn children(&self, id: Id) -> &[Id] {
+        &self.children[id]
+    }
+
+    /// Number of direct and indirect orbits
+    pub fn depth(&self, id: Id) -> usize {
+        self.depths[id]
+    }
+
+    pub fn total_depth(&self) -> usize {
+        self.depths.iter().sum()
+    }
+
+    /// Number of objects orbiting `id` directly or indirectly, plus itself
+    pub fn subtree_size(&self, id: Id) -> usize {
+        self.sizes[id]
     }
-    let mut p = path(orbits[satellite], orbits);
-    p.push(satellite);
-    p
+
+    /// The deepest object that both `a` and `b` orbit, or are
+    pub fn lca(&self, mut a: Id, mut b: Id) -> Id {
+        while self.depths[a] > self.depths[b] {
+            a = self.parents[a].unwrap();
+        }
+        while self.depths[b] > self.depths[a] {
+            b = self.parents[b].unwrap();
+        }
+        while a != b {
+            a = self.parents[a].unwrap();
+            b = self.parents[b].unwrap();
+        }
+        a
+    }
+
+    /// Number of orbits between two objects
+    pub fn distance(&self, a: Id, b: Id) -> usize {
+        self.depths[a] + self.depths[b] - 2 * self.depths[self.lca(a, b)]
+    }
+
+    /// Orbital transfers to move from the object `a` orbits to the one `b`
+    /// orbits, or `None` if either is the root
+    pub fn transfers(&self, a: Id, b: Id) -> Option<usize> {
+        Some(self.distance(self.parents[a]?, self.parents[b]?))
+    }
+
+    /// `id` and every object it orbits, ending at the root
+    pub fn ancestors(&self, id: Id) -> impl Iterator<Item = Id> + '_ {
+        std::iter::successors(Some(id), move |&id| self.parents[id])
+    }
+
+    /// Objects from `a` to `b` inclusive, going through their common center
+    pub fn path(&self, a: Id, b: Id) -> Vec<Id> {
+        let lca = self.lca(a, b);
+        let mut path = self
+            .ancestors(a)
+            .take(self.depths[a] - self.depths[lca] + 1)
+            .collect::<Vec<_>>();
+        let down = self
+            .ancestors(b)
+            .take(self.depths[b] - self.depths[lca])
+            .collect::<Vec<_>>();
+        path.extend(down.into_iter().rev());
+        path
+    }
+
+    fn sorted_children(&self, id: Id) -> Vec<Id> {
+        let mut children = self.children[id].clone();
+        children.sort_by(|&a, &b| self.names[a].cmp(&self.names[b]));
+        children
+    }
+
+    /// Graphviz digraph with an edge from each center to its satellites.
+    /// Objects in `highlight`, and edges between them, are drawn in red.
+    pub fn to_dot(&self, highlight: &[Id]) -> String {
+        let mut marked = vec![false; self.len()];
+        for &id in highlight {
+            marked[id] = true;
+        }
+        let mut dot = String::from("digraph orbits {\n    rankdir=LR;\n");
+        for &id in highlight {
+            dot += &format!("    {:?} [color=red, fontcolor=red];\n", self.names[id]);
+        }
+        let mut stack = vec![self.root];
+        while let Some(id) = stack.pop() {
+            let children = self.sorted_children(id);
+            for &child in &children {
+                dot += &format!("    {:?} -> {:?}", self.names[id], self.names[child]);
+                if marked[id] && marked[child] {
+                    dot += " [color=red, penwidth=2]";
+                }
+                dot += ";\n";
+            }
+            stack.extend(children.into_iter().rev());
+        }
+        dot += "}\n";
+        dot
+    }
+
+    /// Indented tree with satellites below their center, sorted by name.
+    /// Objects in `highlight` are marked with `*`.
+    pub fn to_tree(&self, highlight: &[Id]) -> String {
+        let mut marked = vec![false; self.len()];
+        for &id in highlight {
+            marked[id] = true;
+        }
+        let mut tree = String::new();
+        // (object, indent for its satellites, line prefix for itself)
+        let mut stack = vec![(self.root, String::new(), String::new())];
+        while let Some((id, indent, prefix)) = stack.pop() {
+            tree += &prefix;
+            tree += &self.names[id];
+            if marked[id] {
+                tree += " *";
+            }
+            tree.push('\n');
+            let children = self.sorted_children(id);
+            let last = children.len().saturating_sub(1);
+            for (i, &child) in children.iter().enumerate().rev() {
+                let (branch, next) = if i == last {
+                    ("`-- ", "    ")
+                } else {
+                    ("|-- ", "|   ")
+                };
+                stack.push((
+                    child,
+                    format!("{}{}", indent, next),
+                    format!("{}{}", indent, branch),
+                ));
+            }
+        }
+        tree
+    }
+}
+
+fn part1(orbits: &OrbitMap) -> usize {
+    orbits.total_depth()
 }
 
-fn part2(orbits: &HashMap<&str, &str>) -> usize {
-    let path_san = path("SAN", &orbits);
-    let path_you = path("YOU", &orbits);
-
-    let common = path_san
-        .iter()
-        .zip(path_you.iter())
-        .take_while(|(a, b)| a == b)
-        .count();
-    let common_to_san = path_san.len() - common - 1;
-    let common_to_you = path_you.len() - common - 1;
-    common_to_san + common_to_you
+/// Every object from YOU to SAN, for highlighting the transfers in `part2`
+pub fn you_to_san(orbits: &OrbitMap) -> Result<Vec<Id>, OrbitError> {
+    Ok(orbits.path(orbits.lookup("YOU")?, orbits.lookup("SAN")?))
+}
+
+fn part2(orbits: &OrbitMap) -> usize {
+    let you = orbits.lookup("YOU").unwrap();
+    let san = orbits.lookup("SAN").unwrap();
+    orbits.transfers(you, san).unwrap()
+}
+
+#[cfg(test)]
+static EXAMPLE: &str = "COM)B\nB)C\nC)D\nD)E\nE)F\nB)G\nG)H\nD)I\nE)J\nJ)K\nK)L\n";
+
+#[test]
+fn test_part1() {
+    let orbits = OrbitMap::parse(EXAMPLE).unwrap();
+    assert_eq!(part1(&orbits), 42);
+    assert_eq!(orbits.name(orbits.root()), "COM");
+    assert_eq!(orbits.depth(orbits.id("L").unwrap()), 7);
+    assert_eq!(orbits.subtree_size(orbits.root()), 12);
+    assert_eq!(orbits.subtree_size(orbits.id("E").unwrap()), 5);
+}
+
+#[test]
+fn test_part2() {
+    let input = format!("{}K)YOU\nI)SAN\n", EXAMPLE);
+    let orbits = OrbitMap::parse(&input).unwrap();
+    assert_eq!(part2(&orbits), 4);
+
+    let id = |name| orbits.id(name).unwrap();
+    assert_eq!(orbits.lca(id("YOU"), id("SAN")), id("D"));
+    assert_eq!(orbits.lca(id("L"), id("E")), id("E"));
+    assert_eq!(orbits.distance(id("H"), id("F")), 6);
+    assert_eq!(orbits.transfers(id("COM"), id("SAN")), None);
+
+    let names = |path: Vec<Id>| {
+        path.into_iter()
+            .map(|id| orbits.name(id))
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(
+        names(orbits.path(id("YOU"), id("SAN"))),
+        vec!["YOU", "K", "J", "E", "D", "I", "SAN"]
+    );
+    assert_eq!(names(orbits.path(id("C"), id("E"))), vec!["C", "D", "E"]);
+    assert_eq!(names(orbits.path(id("H"), id("H"))), vec!["H"]);
+
+    // any object can serve as the root of the part of the map it owns
+    let sub = OrbitMap::parse_rooted("E)J\nJ)K\nK)L\n", "E").unwrap();
+    assert_eq!(sub.total_depth(), 6);
+}
+
+#[test]
+fn test_orbit_errors() {
+    let err = |input, root: Option<&str>| match root {
+        Some(root) => OrbitMap::parse_rooted(input, root).unwrap_err(),
+        None => OrbitMap::parse(input).unwrap_err(),
+    };
+    assert_eq!(
+        err("COM)A\nA-B\n", None),
+        OrbitError::Syntax {
+            line: 2,
+            text: "A-B".to_owned()
+        }
+    );
+    assert_eq!(
+        err("COM)A\nB)A\n", None),
+        OrbitError::TwoCenters {
+            object: "A".to_owned(),
+            first: "COM".to_owned(),
+            second: "B".to_owned()
+        }
+    );
+    assert_eq!(err("", None), OrbitError::NoRoot);
+    assert_eq!(
+        err("COM)A\nX)Y\n", None),
+        OrbitError::MultipleRoots(vec!["COM".to_owned(), "X".to_owned()])
+    );
+    assert_eq!(
+        err("COM)A\nX)Y\nY)Z\n", Some("COM")),
+        OrbitError::Orphans(vec!["X".to_owned(), "Y".to_owned(), "Z".to_owned()])
+    );
+    assert_eq!(
+        err("COM)A\nA)B\n", Some("A")),
+        OrbitError::Orphans(vec!["COM".to_owned()])
+    );
+    assert_eq!(
+        err("COM)A\n", Some("SUN")),
+        OrbitError::UnknownObject("SUN".to_owned())
+    );
+    assert_eq!(
+        err("COM)A\nB)C\nC)D\nD)B\n", None),
+        OrbitError::Cycle(vec![
+            "C".to_owned(),
+            "D".to_owned(),
+            "B".to_owned(),
+            "C".to_owned()
+        ])
+    );
+    assert_eq!(
+        err("A)B\nB)A\n", None).to_string(),
+        "orbits form a cycle: B)A)B"
+    );
+}
+
+#[test]
+fn test_export() {
+    let input = "COM)B\nB)C\nB)YOU\nCOM)SAN\n";
+    let orbits = OrbitMap::parse(input).unwrap();
+    let path = you_to_san(&orbits).unwrap();
+    assert_eq!(
+        orbits.to_tree(&path),
+        "COM *\n|-- B *\n|   |-- C\n|   `-- YOU *\n`-- SAN *\n"
+    );
+    assert_eq!(
+        orbits.to_dot(&[]),
+        "digraph orbits {\n    rankdir=LR;\n    \"COM\" -> \"B\";\n    \"COM\" -> \"SAN\";\n    \
+         \"B\" -> \"C\";\n    \"B\" -> \"YOU\";\n}\n"
+    );
+    let dot = orbits.to_dot(&path);
+    assert!(dot.contains("    \"YOU\" [color=red, fontcolor=red];\n"));
+    assert!(dot.contains("    \"B\" -> \"YOU\" [color=red, penwidth=2];\n"));
+    assert!(dot.contains("    \"B\" -> \"C\";\n"));
+    assert_eq!(
+        you_to_san(&OrbitMap::parse(EXAMPLE).unwrap()),
+        Err(OrbitError::UnknownObject("YOU".to_owned()))
+    );
+}
+
+#[test]
+fn test_deep_chain() {
+    const LEN: usize = 1_000_000;
+    let input = (0..LEN)
+        .map(|i| format!("{}){}\n", i, i + 1))
+        .collect::<String>();
+    let orbits = OrbitMap::parse(&input).unwrap();
+    let last = orbits.id(&LEN.to_string()).unwrap();
+    let middle = orbits.id(&(LEN / 2).to_string()).unwrap();
+    assert_eq!(orbits.total_depth(), LEN * (LEN + 1) / 2);
+    assert_eq!(orbits.depth(last), LEN);
+    assert_eq!(orbits.subtree_size(orbits.root()), LEN + 1);
+    assert_eq!(orbits.distance(last, orbits.root()), LEN);
+    assert_eq!(orbits.lca(last, middle), middle);
+
+    let path = orbits.path(last, orbits.root());
+    assert_eq!(path.len(), LEN + 1);
+    assert_eq!(orbits.name(path[1]), (LEN - 1).to_string());
+
+    // a loop this long is still reported rather than overflowing the stack
+    let input = format!("{}{})0\n", input, LEN);
+    match OrbitMap::parse(&input) {
+        Err(OrbitError::Cycle(cycle)) => assert_eq!(cycle.len(), LEN + 2),
+        other => panic!("expected a cycle, got {:?}", other.map(|map| map.len())),
+    }
 }
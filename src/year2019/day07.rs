@@ -1,3 +1,4 @@
+use crate::year2019::combinatorics::{par_argmax, permutations};
 use crate::year2019::intcode::{Computer, Word};
 
 pub fn run() {
@@ -5,101 +6,236 @@ pub fn run() {
         .map(Computer::decode)
         .unwrap();
 
-    println!("year2019 day07 part1 {}", part1(&code));
-    println!("year2019 day07 part2 {}", part2(&code));
+    let verbose = std::env::args().any(|arg| arg == "--verbose");
+
+    let series = part1(&code);
+    println!("year2019 day07 part1 {}", series.thrust());
+    if verbose {
+        print!("{}", series);
+    }
+    let feedback = part2(&code);
+    println!("year2019 day07 part2 {}", feedback.thrust());
+    if verbose {
+        print!("{}", feedback);
+    }
 }
 
-fn permutations<T: Clone>(choices: &[T]) -> Vec<Vec<T>> {
-    if choices.len() == 1 {
-        vec![vec![choices[0].clone()]]
-    } else {
-        (0..choices.len())
-            .into_iter()
-            .map(|i| {
-                let mut new_choices = choices.to_vec();
-                new_choices.remove(i);
-                let mut p = permutations(&new_choices);
-                p.iter_mut().for_each(|p| p.push(choices[i].clone()));
-                p
-            })
-            .flatten()
-            .collect()
+/// Everything that happened while running one phase setting
+#[derive(Clone, Debug, PartialEq)]
+pub struct Amplification {
+    pub phases: Vec<Word>,
+    /// The signal each amplifier sent on, one row per trip around the loop
+    pub signals: Vec<Vec<Word>>,
+}
+
+impl Amplification {
+    /// Signal sent to the thrusters by the last amplifier
+    pub fn thrust(&self) -> Word {
+        *self.signals.last().unwrap().last().unwrap()
+    }
+
+    pub fn rounds(&self) -> usize {
+        self.signals.len()
     }
 }
 
-fn part1(code: &[Word]) -> Word {
-    permutations(&[0 as Word, 1, 2, 3, 4])
-        .into_iter()
-        .map(|phases| {
-            phases.into_iter().fold(0 as Word, |input, phase| {
-                Computer::run_program(code, &[phase, input])[0]
-            })
-        })
-        .max()
-        .unwrap()
+impl std::fmt::Display for Amplification {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let phases = self.phases.iter().map(Word::to_string).collect::<Vec<_>>();
+        writeln!(
+            f,
+            "phases {} over {} round(s)",
+            phases.join(","),
+            self.rounds()
+        )?;
+        for (i, round) in self.signals.iter().enumerate() {
+            let signals = round.iter().map(Word::to_string).collect::<Vec<_>>();
+            writeln!(f, "  round {}: {}", i + 1, signals.join(" -> "))?;
+        }
+        Ok(())
+    }
 }
 
-fn part2(code: &[Word]) -> Word {
-    permutations(&[5 as Word, 6, 7, 8, 9])
-        .into_iter()
-        .map(|phases| {
-            let mut computers = phases
-                .into_iter()
-                .map(|phase| Computer::with_program(code, &[phase]))
-                .collect::<Vec<_>>();
-            let mut input = 0;
-            loop {
-                for i in 0..5 {
-                    computers[i].get_input().push_back(input);
-                    let done = computers[i].exec();
-                    input = *computers[i].get_output().last().unwrap();
-                    if i == 4 && done {
-                        return input;
-                    }
-                }
-            }
-        })
-        .max()
-        .unwrap()
+/// Run the amplifiers in a feedback loop until the last one halts. Programs
+/// that halt after one signal make this a single pass through the series.
+fn feedback_loop(code: &[Word], phases: &[Word]) -> Amplification {
+    let mut computers = phases
+        .iter()
+        .map(|&phase| Computer::with_program(code, &[phase]))
+        .collect::<Vec<_>>();
+    let mut signal = 0;
+    let mut signals = Vec::new();
+    loop {
+        let mut done = false;
+        let mut round = Vec::with_capacity(computers.len());
+        for computer in &mut computers {
+            computer.get_input().push_back(signal);
+            done = computer.exec();
+            signal = *computer.get_output().last().unwrap();
+            round.push(signal);
+        }
+        signals.push(round);
+        if done {
+            return Amplification {
+                phases: phases.to_vec(),
+                signals,
+            };
+        }
+    }
+}
+
+/// Try every order of `phases`, returning the run with the strongest thrust
+fn best_amplification(code: &[Word], phases: &[Word]) -> Amplification {
+    let (phases, _) = par_argmax(permutations(phases), |phases| {
+        feedback_loop(code, phases).thrust()
+    })
+    .unwrap();
+    feedback_loop(code, &phases)
+}
+
+fn part1(code: &[Word]) -> Amplification {
+    best_amplification(code, &[0, 1, 2, 3, 4])
+}
+
+fn part2(code: &[Word]) -> Amplification {
+    best_amplification(code, &[5, 6, 7, 8, 9])
+}
+
+#[cfg(test)]
+fn summary(amplification: Amplification) -> (Vec<Word>, Word) {
+    (amplification.phases.clone(), amplification.thrust())
 }
 
 #[test]
 fn test_part1() {
     assert_eq!(
-        part1(&[3, 15, 3, 16, 1002, 16, 10, 16, 1, 16, 15, 15, 4, 15, 99, 0, 0]),
-        43210
+        summary(part1(&[
+            3, 15, 3, 16, 1002, 16, 10, 16, 1, 16, 15, 15, 4, 15, 99, 0, 0
+        ])),
+        (vec![4, 3, 2, 1, 0], 43210)
     );
     assert_eq!(
-        part1(&[
+        summary(part1(&[
             3, 23, 3, 24, 1002, 24, 10, 24, 1002, 23, -1, 23, 101, 5, 23, 23, 1, 24, 23, 23, 4, 23,
             99, 0, 0
-        ]),
-        54321
+        ])),
+        (vec![0, 1, 2, 3, 4], 54321)
     );
     assert_eq!(
-        part1(&[
+        summary(part1(&[
             3, 31, 3, 32, 1002, 32, 10, 32, 1001, 31, -2, 31, 1007, 31, 0, 33, 1002, 33, 7, 33, 1,
             33, 31, 31, 1, 32, 31, 31, 4, 31, 99, 0, 0, 0
-        ]),
-        65210
+        ])),
+        (vec![1, 0, 4, 3, 2], 65210)
     );
 }
 
 #[test]
 fn test_part2() {
     assert_eq!(
-        part2(&[
+        summary(part2(&[
             3, 26, 1001, 26, -4, 26, 3, 27, 1002, 27, 2, 27, 1, 27, 26, 27, 4, 27, 1001, 28, -1,
             28, 1005, 28, 6, 99, 0, 0, 5
-        ]),
-        139629729
+        ])),
+        (vec![9, 8, 7, 6, 5], 139629729)
     );
     assert_eq!(
-        part2(&[
+        summary(part2(&[
             3, 52, 1001, 52, -5, 52, 3, 53, 1, 52, 56, 54, 1007, 54, 5, 55, 1005, 55, 26, 1001, 54,
             -5, 54, 1105, 1, 12, 1, 53, 54, 53, 1008, 54, 0, 55, 1001, 55, 1, 55, 2, 53, 55, 53, 4,
             53, 1001, 56, -1, 56, 1005, 56, 6, 99, 0, 0, 0, 0, 10
-        ]),
-        18216
+        ])),
+        (vec![9, 7, 8, 5, 6], 18216)
     );
 }
+
+#[test]
+fn test_signal_trace() {
+    let code = &[
+        3, 26, 1001, 26, -4, 26, 3, 27, 1002, 27, 2, 27, 1, 27, 26, 27, 4, 27, 1001, 28, -1, 28,
+        1005, 28, 6, 99, 0, 0, 5,
+    ];
+    let run = feedback_loop(code, &[9, 8, 7, 6, 5]);
+    assert_eq!(run.rounds(), 5);
+    assert_eq!(run.signals[0], vec![5, 14, 31, 64, 129]);
+    assert_eq!(run.thrust(), 139629729);
+    assert!(run
+        .to_string()
+        .starts_with("phases 9,8,7,6,5 over 5 round(s)\n  round 1: 5 -> 14 -> 31 -> 64 -> 129\n"));
+
+    let run = feedback_loop(
+        &[
+            3, 15, 3, 16, 1002, 16, 10, 16, 1, 16, 15, 15, 4, 15, 99, 0, 0,
+        ],
+        &[4, 3, 2, 1, 0],
+    );
+    assert_eq!(run.signals, vec![vec![4, 43, 432, 4321, 43210]]);
+}
+
+#[test]
+fn test_feedback_loop_threaded() {
+    use crate::year2019::intcode::threads;
+
+    fn threaded(code: &[Word], phases: &[Word]) -> Word {
+        let inputs = phases
+            .iter()
+            .enumerate()
+            .map(|(i, &phase)| if i == 0 { vec![phase, 0] } else { vec![phase] })
+            .collect::<Vec<_>>();
+        let machines = inputs
+            .iter()
+            .map(|input| (code, input.as_slice()))
+            .collect::<Vec<_>>();
+        let timeout = std::time::Duration::from_secs(1);
+        let outputs = threads::run_ring(&machines, timeout).unwrap();
+        *outputs.last().unwrap().last().unwrap()
+    }
+
+    let code = &[
+        3, 52, 1001, 52, -5, 52, 3, 53, 1, 52, 56, 54, 1007, 54, 5, 55, 1005, 55, 26, 1001, 54, -5,
+        54, 1105, 1, 12, 1, 53, 54, 53, 1008, 54, 0, 55, 1001, 55, 1, 55, 2, 53, 55, 53, 4, 53,
+        1001, 56, -1, 56, 1005, 56, 6, 99, 0, 0, 0, 0, 10,
+    ];
+    for phases in permutations(&[5, 6, 7, 8, 9]) {
+        assert_eq!(
+            threaded(code, &phases),
+            feedback_loop(code, &phases).thrust()
+        );
+    }
+}
+
+#[test]
+fn test_feedback_loop_async() {
+    use crate::year2019::intcode::futures;
+
+    fn wired(code: &[Word], phases: &[Word]) -> Word {
+        let pipes = phases
+            .iter()
+            .map(|&phase| {
+                let pipe = futures::Pipe::new();
+                pipe.send(phase);
+                pipe
+            })
+            .collect::<Vec<_>>();
+        pipes[0].send(0);
+
+        let mut executor = futures::Executor::new();
+        for (i, input) in pipes.iter().enumerate() {
+            let output = pipes[(i + 1) % pipes.len()].clone();
+            let mut computer = Computer::with_io(code, input.clone(), output);
+            executor.spawn(async move { assert!(computer.run_async().await) });
+        }
+        assert!(executor.run());
+
+        // the first amplifier halts before taking the final signal
+        pipes[0].try_recv().unwrap()
+    }
+
+    let code = &[
+        3, 26, 1001, 26, -4, 26, 3, 27, 1002, 27, 2, 27, 1, 27, 26, 27, 4, 27, 1001, 28, -1, 28,
+        1005, 28, 6, 99, 0, 0, 5,
+    ];
+    for phases in permutations(&[5, 6, 7, 8, 9]) {
+        assert_eq!(wired(code, &phases), feedback_loop(code, &phases).thrust());
+    }
+}
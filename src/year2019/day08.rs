@@ -1,43 +1,263 @@
+use crate::year2019::canvas::Canvas;
+use crate::year2019::ocr;
+
 pub fn run() {
     let input = std::fs::read_to_string("inputs/year2019/day08.txt").unwrap();
-    let digits = input
-        .trim()
-        .chars()
-        .map(|c| c.to_digit(10).unwrap() as u8)
-        .collect::<Vec<_>>();
-    let layers: Vec<&[u8]> = digits.chunks(25 * 6).collect();
-    println!("year2019 day08 part1 {}", part1(&layers));
-    println!("year2019 day08 part2\n{}", part2(&layers));
-}
-
-fn part1(layers: &[&[u8]]) -> usize {
-    let safest = layers
-        .iter()
-        .min_by_key(|t| t.iter().filter(|d| **d == 0).count())
-        .unwrap();
-    let ones = safest.iter().filter(|d| **d == 1).count();
-    let twos = safest.iter().filter(|d| **d == 2).count();
-    ones * twos
+    let image = SifImage::parse(&input, 25, 6).unwrap();
+    println!("year2019 day08 part1 {}", part1(&image));
+    println!("year2019 day08 part2 {}", part2(&image));
+}
+
+pub const BLACK: u8 = 0;
+pub const WHITE: u8 = 1;
+pub const TRANSPARENT: u8 = 2;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum SifError {
+    ZeroSize,
+    /// A character other than a decimal digit, at its index in the input
+    InvalidDigit {
+        index: usize,
+        found: char,
+    },
+    /// The input doesn't split into whole layers
+    Length {
+        len: usize,
+        layer_size: usize,
+    },
 }
 
-fn part2(layers: &[&[u8]]) -> String {
-    let mut buffer = [2; 25 * 6];
-    for layer in layers {
-        for (dest, input) in buffer.iter_mut().zip(layer.iter()) {
-            if *dest == 2 && *input < 2 {
-                *dest = *input;
+impl std::fmt::Display for SifError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            SifError::ZeroSize => write!(f, "width and height must be positive"),
+            SifError::InvalidDigit { index, found } => {
+                write!(f, "invalid digit {:?} at index {}", found, index)
             }
+            SifError::Length { len, layer_size } => write!(
+                f,
+                "{} digits is not a whole number of {}-digit layers",
+                len, layer_size
+            ),
         }
     }
+}
 
-    let mut output = String::new();
-    for line in buffer.chunks(25) {
-        output.push_str("    ");
-        for digit in line {
-            output.push(if *digit == 0 { ' ' } else { 'X' });
+impl std::error::Error for SifError {}
+
+/// Image in the Space Image Format: layers of `width * height` digits, the
+/// first layer in front
+#[derive(Clone, Debug, PartialEq)]
+pub struct SifImage {
+    width: usize,
+    height: usize,
+    data: Vec<u8>,
+}
+
+/// How often each digit appears in a layer
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct LayerStats {
+    pub counts: [usize; 10],
+}
+
+impl LayerStats {
+    pub fn count(&self, digit: u8) -> usize {
+        self.counts[digit as usize]
+    }
+}
+
+impl SifImage {
+    /// Parse digits, ignoring surrounding whitespace
+    pub fn parse(input: &str, width: usize, height: usize) -> Result<SifImage, SifError> {
+        if width == 0 || height == 0 {
+            return Err(SifError::ZeroSize);
+        }
+        let data = input
+            .trim()
+            .chars()
+            .enumerate()
+            .map(|(index, c)| {
+                c.to_digit(10)
+                    .map(|d| d as u8)
+                    .ok_or(SifError::InvalidDigit { index, found: c })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let layer_size = width * height;
+        if data.is_empty() || data.len() % layer_size != 0 {
+            return Err(SifError::Length {
+                len: data.len(),
+                layer_size,
+            });
         }
-        output.push('\n');
+        Ok(SifImage {
+            width,
+            height,
+            data,
+        })
     }
 
-    output
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn layer_count(&self) -> usize {
+        self.data.len() / (self.width * self.height)
+    }
+
+    pub fn layers(&self) -> std::slice::Chunks<'_, u8> {
+        self.data.chunks(self.width * self.height)
+    }
+
+    pub fn layer(&self, i: usize) -> &[u8] {
+        self.layers().nth(i).unwrap()
+    }
+
+    pub fn layer_stats(&self) -> Vec<LayerStats> {
+        self.layers()
+            .map(|layer| {
+                let mut stats = LayerStats::default();
+                for &digit in layer {
+                    stats.counts[digit as usize] += 1;
+                }
+                stats
+            })
+            .collect()
+    }
+
+    /// Each pixel takes the colour of the frontmost layer that isn't
+    /// transparent there, or stays transparent if none is
+    pub fn composite(&self) -> Vec<u8> {
+        let mut pixels = vec![TRANSPARENT; self.width * self.height];
+        for layer in self.layers() {
+            for (pixel, &digit) in pixels.iter_mut().zip(layer) {
+                if *pixel == TRANSPARENT {
+                    *pixel = digit;
+                }
+            }
+        }
+        pixels
+    }
+
+    /// Composited pixels of one colour, lit wherever they match
+    pub fn to_canvas(&self, colour: u8) -> Canvas {
+        let rows = self
+            .composite()
+            .chunks(self.width)
+            .map(|row| row.iter().map(|&d| d == colour).collect())
+            .collect::<Vec<Vec<bool>>>();
+        Canvas::from_rows(&rows)
+    }
+
+    /// Composited pixels as rows of `X` for white and space otherwise
+    pub fn to_text(&self) -> String {
+        self.to_canvas(WHITE).to_text('X', ' ')
+    }
+
+    /// Plain PGM, with transparent pixels left mid-grey and any colours
+    /// beyond the SIF palette as a grey ramp
+    pub fn to_pgm(&self) -> String {
+        let mut pgm = format!("P2\n{} {}\n255\n", self.width, self.height);
+        self.write_rows(&mut pgm, |d| grey(d).to_string());
+        pgm
+    }
+
+    /// Plain PPM, with transparent pixels shown in magenta
+    pub fn to_ppm(&self) -> String {
+        let mut ppm = format!("P3\n{} {}\n255\n", self.width, self.height);
+        self.write_rows(&mut ppm, |d| match d {
+            TRANSPARENT => "255 0 255".to_owned(),
+            d => {
+                let g = grey(d);
+                format!("{} {} {}", g, g, g)
+            }
+        });
+        ppm
+    }
+
+    /// Plain PBM, where only black pixels are set
+    pub fn to_pbm(&self) -> String {
+        self.to_canvas(BLACK).to_pbm()
+    }
+
+    fn write_rows<F: Fn(u8) -> String>(&self, output: &mut String, sample: F) {
+        for row in self.composite().chunks(self.width) {
+            let row = row.iter().map(|&d| sample(d)).collect::<Vec<_>>();
+            output.push_str(&row.join(" "));
+            output.push('\n');
+        }
+    }
+}
+
+fn grey(digit: u8) -> u8 {
+    match digit {
+        BLACK => 0,
+        WHITE => 255,
+        TRANSPARENT => 128,
+        d => (d as usize * 255 / 9) as u8,
+    }
+}
+
+fn part1(image: &SifImage) -> usize {
+    let safest = image
+        .layer_stats()
+        .into_iter()
+        .min_by_key(|stats| stats.count(0))
+        .unwrap();
+    safest.count(1) * safest.count(2)
+}
+
+/// The message, or the image itself if it can't be read
+fn part2(image: &SifImage) -> String {
+    ocr::recognize(&image.to_canvas(WHITE))
+        .unwrap_or_else(|err| format!("{}\n{}", err, image.to_text()))
+}
+
+#[test]
+fn test_part1() {
+    let image = SifImage::parse("123456789012\n", 3, 2).unwrap();
+    assert_eq!(image.layer_count(), 2);
+    assert_eq!(image.layer(1), &[7, 8, 9, 0, 1, 2]);
+    assert_eq!(image.layer_stats()[0].count(1), 1);
+    assert_eq!(part1(&image), 1);
+}
+
+#[test]
+fn test_composite() {
+    let image = SifImage::parse("0222112222120000", 2, 2).unwrap();
+    assert_eq!(image.composite(), vec![0, 1, 1, 0]);
+    assert_eq!(image.to_text(), " X\nX \n");
+    assert_eq!(part2(&image), "no font is 2 pixels high\n X\nX \n");
+
+    let image = SifImage::parse("0212", 2, 1).unwrap();
+    assert_eq!(image.composite(), vec![0, 2]);
+    assert_eq!(image.to_pgm(), "P2\n2 1\n255\n0 128\n");
+    assert_eq!(image.to_ppm(), "P3\n2 1\n255\n0 0 0 255 0 255\n");
+    assert_eq!(image.to_pbm(), "P1\n2 1\n1 0\n");
+}
+
+#[test]
+fn test_parse_errors() {
+    assert_eq!(SifImage::parse("0000", 0, 2), Err(SifError::ZeroSize));
+    assert_eq!(
+        SifImage::parse("01a1", 2, 2),
+        Err(SifError::InvalidDigit {
+            index: 2,
+            found: 'a'
+        })
+    );
+    assert_eq!(
+        SifImage::parse("01210", 2, 2),
+        Err(SifError::Length {
+            len: 5,
+            layer_size: 4
+        })
+    );
+    assert_eq!(
+        SifImage::parse("", 2, 2).unwrap_err().to_string(),
+        "0 digits is not a whole number of 4-digit layers"
+    );
 }
@@ -1,23 +1,29 @@
+use crate::year2019::canvas::Canvas;
+use crate::year2019::combinatorics::par_argmax;
+use std::collections::{HashMap, HashSet};
+
 pub fn run() {
     let grid = std::fs::read_to_string("inputs/year2019/day10.txt")
         .map(|s| Grid::from_str(&s))
         .unwrap();
 
     println!("year2019 day10 part1 {}", part1(&grid));
-    println!("year2019 day10 part2 {}", part2(&grid));
+    println!("year2019 day10 part2 {}", part2(&grid).unwrap());
 }
 
 fn part1(grid: &Grid) -> usize {
     grid.best_station().1
 }
 
-fn part2(grid: &Grid) -> isize {
-    let (x, y) = grid.vaporization_order(grid.best_station().0)[199];
-    x * 100 + y
+/// Where the 200th asteroid to be vaporized is, if there are that many
+fn part2(grid: &Grid) -> Option<isize> {
+    let (x, y) = Laser::new(grid.best_station().0).nth(grid, 199)?;
+    Some(x * 100 + y)
 }
 
-struct Grid {
-    data: Vec<Vec<bool>>,
+/// Asteroid field, with asteroids marked `#` in the input
+pub struct Grid {
+    data: Canvas,
     asteroids: Vec<Pos>,
     width: isize,
     height: isize,
@@ -29,41 +35,69 @@ impl Grid {
         let height = input.lines().count();
         assert!(input.lines().all(|t| t.len() == width));
 
+        let rows = input
+            .lines()
+            .map(|line| line.chars().map(|c| c == '#').collect())
+            .collect::<Vec<Vec<bool>>>();
         Grid {
-            data: input
-                .lines()
-                .map(|line| line.chars().map(|c| c == '#').collect())
-                .collect(),
+            data: Canvas::from_rows(&rows),
             asteroids: input
                 .lines()
                 .enumerate()
-                .map(|(y, line)| {
+                .flat_map(|(y, line)| {
                     line.chars()
                         .enumerate()
                         .filter(|(_, c)| *c == '#')
                         .map(move |(x, _)| (x as isize, y as isize))
                 })
-                .flatten()
                 .collect(),
             width: width as isize,
             height: height as isize,
         }
     }
 
-    fn contains(&self, pos: Pos) -> bool {
+    pub fn width(&self) -> isize {
+        self.width
+    }
+
+    pub fn height(&self) -> isize {
+        self.height
+    }
+
+    pub fn asteroids(&self) -> &[Pos] {
+        &self.asteroids
+    }
+
+    pub fn canvas(&self) -> &Canvas {
+        &self.data
+    }
+
+    pub fn contains(&self, pos: Pos) -> bool {
         pos.0 >= 0 && pos.0 < self.width && pos.1 >= 0 && pos.1 < self.height
     }
 
-    /// Count number of asteroids visible from this starting point
-    fn visibility(&self, start: Pos) -> usize {
+    /// Count asteroids visible from this starting point: one along each
+    /// distinct line of sight
+    pub fn visibility(&self, start: Pos) -> usize {
+        self.asteroids
+            .iter()
+            .filter_map(|&asteroid| Direction::between(start, asteroid))
+            .collect::<HashSet<_>>()
+            .len()
+    }
+
+    /// Count by marking everything hidden behind each asteroid, for
+    /// checking `visibility`
+    #[cfg(test)]
+    fn visibility_raymarch(&self, start: Pos) -> usize {
         let mut visible = self.data.clone();
-        visible[start.1 as usize][start.0 as usize] = false;
+        visible.set(start.0 as usize, start.1 as usize, false);
 
         for &asteroid in &self.asteroids {
             if asteroid == start {
                 continue;
             }
-            if !visible[asteroid.1 as usize][asteroid.0 as usize] {
+            if !visible.get(asteroid.0 as usize, asteroid.1 as usize) {
                 continue;
             }
 
@@ -75,17 +109,18 @@ impl Grid {
             diff.0 /= gcd;
             diff.1 /= gcd;
 
-            curr = add(curr, diff);
+            curr = (curr.0 + diff.0, curr.1 + diff.1);
             while self.contains(curr) {
-                visible[curr.1 as usize][curr.0 as usize] = false;
-                curr = add(curr, diff);
+                visible.set(curr.0 as usize, curr.1 as usize, false);
+                curr = (curr.0 + diff.0, curr.1 + diff.1);
             }
         }
-        visible.iter().flatten().filter(|p| **p).count()
+        visible.count_lit()
     }
 
-    /// Find the asteroid that can view the most other asteroids
-    fn best_station(&self) -> (Pos, usize) {
+    /// Find the asteroid that can view the most other asteroids, taking the
+    /// last on a tie, as before
+    pub fn best_station(&self) -> (Pos, usize) {
         self.asteroids
             .iter()
             .map(|&pos| (pos, self.visibility(pos)))
@@ -93,23 +128,21 @@ impl Grid {
             .unwrap()
     }
 
-    /// Calculate lists of asteroids at each heading from start
-    fn angles(&self, start: Pos) -> Vec<(TotalF32, Vec<Pos>)> {
-        let mut angles = std::collections::HashMap::<_, Vec<Pos>>::new();
+    /// `best_station`, checking candidates on every core
+    pub fn best_station_parallel(&self) -> (Pos, usize) {
+        par_argmax(self.asteroids.iter().cloned(), |&pos| self.visibility(pos)).unwrap()
+    }
+
+    /// Calculate lists of asteroids at each heading from start, clockwise
+    /// from straight up
+    pub fn angles(&self, start: Pos) -> Vec<(Direction, Vec<Pos>)> {
+        let mut angles = std::collections::BTreeMap::<_, Vec<Pos>>::new();
         for &asteroid in &self.asteroids {
-            if asteroid == start {
-                continue;
+            if let Some(dir) = Direction::between(start, asteroid) {
+                angles.entry(dir).or_default().push(asteroid);
             }
-
-            let dx = (asteroid.0 - start.0) as f32;
-            let dy = (asteroid.1 - start.1) as f32;
-            // atan calculates relative to X axis, we want relative to Y axis
-            let angle = (-dx).atan2(dy);
-            let list = angles.entry(TotalF32(angle)).or_default();
-            list.push(asteroid);
         }
         let mut output = angles.into_iter().collect::<Vec<_>>();
-        output.sort_by_key(|&(a, _)| a);
         output
             .iter_mut() // sort each list by negative distance so closest is first to pop()
             .for_each(|(_, list)| list.sort_by_key(|p| -dist(*p, start)));
@@ -117,30 +150,210 @@ impl Grid {
     }
 
     /// Determine what order to destroy asteroids based on given start
-    fn vaporization_order(&self, start: Pos) -> Vec<Pos> {
-        let mut angles = self.angles(start);
-        let mut output = Vec::new();
-
-        let mut done = false;
-        while !done {
-            done = true;
-
-            for (_, in_line) in &mut angles {
-                if let Some(closest) = in_line.pop() {
-                    done = false;
-                    output.push(closest);
-                }
+    pub fn vaporization_order(&self, start: Pos) -> Vec<Pos> {
+        Laser::new(start).order(self)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Rotation {
+    Clockwise,
+    Counterclockwise,
+}
+
+/// Giant laser on a monitoring station, hitting the nearest asteroid along
+/// each heading as it turns
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Laser {
+    pub station: Pos,
+    pub rotation: Rotation,
+    /// Where the laser starts pointing; an asteroid right on it is hit first
+    pub heading: Direction,
+}
+
+impl Laser {
+    /// Start pointing up and turn clockwise, as in the puzzle
+    pub fn new(station: Pos) -> Laser {
+        Laser {
+            station,
+            rotation: Rotation::Clockwise,
+            heading: Direction::UP,
+        }
+    }
+
+    /// Asteroids hit on each full turn of the laser, in the order they go
+    pub fn sweeps(&self, grid: &Grid) -> Vec<Vec<Pos>> {
+        let mut angles = grid.angles(self.station);
+        match self.rotation {
+            Rotation::Clockwise => {
+                let first = angles.partition_point(|&(dir, _)| dir < self.heading);
+                angles.rotate_left(first);
+            }
+            Rotation::Counterclockwise => {
+                let past = angles.partition_point(|&(dir, _)| dir <= self.heading);
+                angles[..past].reverse();
+                angles[past..].reverse();
             }
         }
 
-        output
+        let mut sweeps = Vec::new();
+        loop {
+            let sweep = angles
+                .iter_mut()
+                .filter_map(|(_, in_line)| in_line.pop())
+                .collect::<Vec<_>>();
+            if sweep.is_empty() {
+                return sweeps;
+            }
+            sweeps.push(sweep);
+        }
+    }
+
+    pub fn order(&self, grid: &Grid) -> Vec<Pos> {
+        self.sweeps(grid).into_iter().flatten().collect()
+    }
+
+    /// The asteroid vaporized after `n` others, if there are enough
+    pub fn nth(&self, grid: &Grid, n: usize) -> Option<Pos> {
+        self.order(grid).get(n).cloned()
+    }
+}
+
+/// How many asteroids each asteroid can see, and the most any can
+fn visibility_counts(grid: &Grid) -> (HashMap<Pos, usize>, usize) {
+    let counts = grid
+        .asteroids()
+        .iter()
+        .map(|&pos| (pos, grid.visibility(pos)))
+        .collect::<HashMap<_, _>>();
+    let most = counts.values().cloned().max().unwrap_or(0);
+    (counts, most)
+}
+
+/// Visibility from each asteroid as a digit scaled so 9 is the best, with
+/// the station drawn as `@`
+pub fn render_heatmap(grid: &Grid, station: Pos) -> String {
+    let (counts, most) = visibility_counts(grid);
+    let mut output = String::new();
+    for y in 0..grid.height() {
+        for x in 0..grid.width() {
+            output.push(match counts.get(&(x, y)) {
+                _ if (x, y) == station => '@',
+                Some(&count) => {
+                    std::char::from_digit((count * 9 / most.max(1)) as u32, 10).unwrap()
+                }
+                None => '.',
+            });
+        }
+        output.push('\n');
     }
+    output
 }
 
-type Pos = (isize, isize);
-fn add(lhs: Pos, rhs: Pos) -> Pos {
-    (lhs.0 + rhs.0, lhs.1 + rhs.1)
+/// Each asteroid numbered by when the laser vaporizes it, counting from 1
+pub fn render_order(grid: &Grid, laser: &Laser) -> String {
+    let order = laser
+        .order(grid)
+        .into_iter()
+        .enumerate()
+        .map(|(i, pos)| (pos, i + 1))
+        .collect::<HashMap<_, _>>();
+    let width = order.len().max(1).to_string().len();
+    let mut output = String::new();
+    for y in 0..grid.height() {
+        let row = (0..grid.width())
+            .map(|x| match order.get(&(x, y)) {
+                _ if (x, y) == laser.station => format!("{:>1$}", "@", width),
+                Some(n) => format!("{:>1$}", n, width),
+                None => format!("{:>1$}", ".", width),
+            })
+            .collect::<Vec<_>>();
+        output.push_str(&row.join(" "));
+        output.push('\n');
+    }
+    output
 }
+
+/// Asteroids coloured from blue to red by how many others they see and
+/// numbered in vaporization order, with the first sweep drawn out from the
+/// station
+pub fn render_svg(grid: &Grid, laser: &Laser) -> String {
+    use std::fmt::Write;
+
+    const CELL: isize = 10;
+    let centre = |(x, y): Pos| (x * CELL + CELL / 2, y * CELL + CELL / 2);
+    let (counts, most) = visibility_counts(grid);
+    let sweeps = laser.sweeps(grid);
+
+    let mut svg = String::new();
+    writeln!(
+        svg,
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" viewBox=\"0 0 {} {}\" font-family=\"sans-serif\">",
+        grid.width() * CELL,
+        grid.height() * CELL
+    )
+    .unwrap();
+    writeln!(
+        svg,
+        "  <rect width=\"100%\" height=\"100%\" fill=\"black\"/>"
+    )
+    .unwrap();
+
+    let (sx, sy) = centre(laser.station);
+    for &pos in sweeps.first().into_iter().flatten() {
+        let (x, y) = centre(pos);
+        writeln!(
+            svg,
+            "  <line x1=\"{}\" y1=\"{}\" x2=\"{}\" y2=\"{}\" stroke=\"#ffffff40\" stroke-width=\"0.5\"/>",
+            sx, sy, x, y
+        )
+        .unwrap();
+    }
+
+    let order = sweeps.iter().flatten().enumerate();
+    let order = order
+        .map(|(i, &pos)| (pos, i + 1))
+        .collect::<HashMap<_, _>>();
+    for &pos in grid.asteroids() {
+        let (x, y) = centre(pos);
+        let count = counts[&pos];
+        let hue = 240 - 240 * count / most.max(1);
+        let (stroke, label) = if pos == laser.station {
+            ("white", "@".to_owned())
+        } else {
+            (
+                "none",
+                order.get(&pos).map_or(String::new(), usize::to_string),
+            )
+        };
+        writeln!(
+            svg,
+            "  <circle cx=\"{}\" cy=\"{}\" r=\"{}\" fill=\"hsl({}, 80%, 50%)\" stroke=\"{}\"><title>{},{} sees {}</title></circle>",
+            x,
+            y,
+            CELL * 2 / 5,
+            hue,
+            stroke,
+            pos.0,
+            pos.1,
+            count
+        )
+        .unwrap();
+        writeln!(
+            svg,
+            "  <text x=\"{}\" y=\"{}\" font-size=\"{}\" text-anchor=\"middle\" dominant-baseline=\"central\" fill=\"white\">{}</text>",
+            x,
+            y,
+            CELL * 3 / 10,
+            label
+        )
+        .unwrap();
+    }
+    svg.push_str("</svg>\n");
+    svg
+}
+
+pub type Pos = (isize, isize);
 fn sub(lhs: Pos, rhs: Pos) -> Pos {
     (lhs.0 - rhs.0, lhs.1 - rhs.1)
 }
@@ -157,28 +370,56 @@ fn gcd(a: isize, b: isize) -> isize {
     }
 }
 
-#[derive(Clone, Copy, Debug, PartialEq, PartialOrd)]
-pub struct TotalF32(f32);
-impl TotalF32 {
-    fn normalize(&self) -> f32 {
-        if self.0.is_normal() {
-            self.0
-        } else if self.0.abs() == 0.0 {
-            0.0
+/// Heading from one position to another as the smallest whole step, so
+/// everything along the same line of sight shares one value. Orders
+/// clockwise starting from straight up, where y grows downwards.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Direction {
+    dx: isize,
+    dy: isize,
+}
+
+impl Direction {
+    pub const UP: Direction = Direction { dx: 0, dy: -1 };
+
+    /// `None` when both positions are the same
+    pub fn between(from: Pos, to: Pos) -> Option<Direction> {
+        let (dx, dy) = sub(to, from);
+        if (dx, dy) == (0, 0) {
+            return None;
+        }
+        let gcd = gcd(dx, dy).abs();
+        Some(Direction {
+            dx: dx / gcd,
+            dy: dy / gcd,
+        })
+    }
+
+    pub fn step(&self) -> Pos {
+        (self.dx, self.dy)
+    }
+
+    /// 0 from straight up round to just before straight down, 1 for the rest
+    fn half(&self) -> u8 {
+        if self.dx > 0 || (self.dx == 0 && self.dy < 0) {
+            0
         } else {
-            panic!();
+            1
         }
     }
 }
-impl std::cmp::Ord for TotalF32 {
+
+impl Ord for Direction {
     fn cmp(&self, other: &Self) -> std::cmp::Ordering {
-        PartialOrd::partial_cmp(&self.0, &other.0).unwrap()
+        // within a half turn, `self` comes first if `other` is clockwise of it
+        let cross = self.dx * other.dy - self.dy * other.dx;
+        self.half().cmp(&other.half()).then(0.cmp(&cross))
     }
 }
-impl std::cmp::Eq for TotalF32 {}
-impl std::hash::Hash for TotalF32 {
-    fn hash<H: std::hash::Hasher>(&self, hasher: &mut H) {
-        hasher.write_u32(self.normalize() as u32)
+
+impl PartialOrd for Direction {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
     }
 }
 
@@ -191,6 +432,99 @@ fn test_gcd() {
     assert_eq!(gcd(-5, -10), -5);
     assert_eq!(gcd(-5, -10), -5);
 }
+#[test]
+fn test_directions() {
+    let dir = |dx, dy| Direction::between((0, 0), (dx, dy)).unwrap();
+    let compass = [
+        (0, -1),
+        (1, -1),
+        (1, 0),
+        (1, 1),
+        (0, 1),
+        (-1, 1),
+        (-1, 0),
+        (-1, -1),
+    ];
+    for (i, &a) in compass.iter().enumerate() {
+        for (j, &b) in compass.iter().enumerate() {
+            assert_eq!(dir(a.0, a.1).cmp(&dir(b.0, b.1)), i.cmp(&j));
+        }
+    }
+
+    assert_eq!(Direction::between((3, 3), (3, 3)), None);
+    assert_eq!(dir(-4, 6), dir(-2, 3));
+    assert_eq!(dir(-4, 6).step(), (-2, 3));
+    // nearly parallel lines of sight stay apart on large grids
+    assert!(dir(1_000_000, -999_999) < dir(999_999, -999_998));
+    assert!(dir(1, -1_000_000) > dir(0, -1));
+    assert!(dir(-1, -1_000_000) > dir(-1, -999_999));
+}
+
+#[cfg(test)]
+fn random_grid(seed: u64, width: usize, height: usize, per_mille: usize) -> Grid {
+    let mut rng = crate::year2019::testing::Rng::new(seed);
+    let input = (0..height)
+        .map(|_| {
+            (0..width)
+                .map(|_| {
+                    if rng.below(1000) < per_mille {
+                        '#'
+                    } else {
+                        '.'
+                    }
+                })
+                .collect::<String>()
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+    Grid::from_str(&input)
+}
+
+#[test]
+fn test_visibility() {
+    for seed in 0..4 {
+        let grid = random_grid(seed, 30, 20, 300);
+        for &pos in grid.asteroids() {
+            assert_eq!(grid.visibility(pos), grid.visibility_raymarch(pos));
+        }
+        assert_eq!(grid.best_station_parallel(), grid.best_station());
+    }
+}
+
+#[test]
+#[ignore]
+fn bench_visibility() {
+    // the raymarch scans the whole field per station, so it wins on small
+    // dense fields but falls behind once the field is large and sparse
+    for &(size, per_mille) in &[(100, 100), (300, 20), (1000, 2)] {
+        let grid = random_grid(7, size, size, per_mille);
+        let start = std::time::Instant::now();
+        let raymarch = grid
+            .asteroids()
+            .iter()
+            .map(|&pos| grid.visibility_raymarch(pos))
+            .max();
+        let raymarch_time = start.elapsed();
+        let start = std::time::Instant::now();
+        let (_, best) = grid.best_station();
+        let directions_time = start.elapsed();
+        let start = std::time::Instant::now();
+        let (_, parallel) = grid.best_station_parallel();
+        let parallel_time = start.elapsed();
+
+        assert_eq!(raymarch, Some(best));
+        assert_eq!(parallel, best);
+        println!(
+            "{0}x{0}, {1} asteroids: raymarch {2:?}, directions {3:?}, parallel {4:?}",
+            size,
+            grid.asteroids().len(),
+            raymarch_time,
+            directions_time,
+            parallel_time
+        );
+    }
+}
+
 #[test]
 fn test_part1() {
     assert_eq!(
@@ -305,6 +639,114 @@ fn test_part2() {
              #.#.#.#####.####.###\n\
              ###.##.####.##.#..##",
         )),
-        802
+        Some(802)
+    );
+}
+
+#[test]
+fn test_laser() {
+    let grid = Grid::from_str(
+        ".#..##.###...#######\n\
+         ##.############..##.\n\
+         .#.######.########.#\n\
+         .###.#######.####.#.\n\
+         #####.##.#.##.###.##\n\
+         ..#####..#.#########\n\
+         ####################\n\
+         #.####....###.#.#.##\n\
+         ##.#################\n\
+         #####.##.###..####..\n\
+         ..######..##.#######\n\
+         ####.##.####...##..#\n\
+         .#####..#.######.###\n\
+         ##...#.##########...\n\
+         #.##########.#######\n\
+         .####.#.###.###.#.##\n\
+         ....##.##.###..#####\n\
+         .#.#.###########.###\n\
+         #.#.#.#####.####.###\n\
+         ###.##.####.##.#..##",
+    );
+    let laser = Laser::new((11, 13));
+    for &(n, pos) in &[
+        (1, (11, 12)),
+        (2, (12, 1)),
+        (3, (12, 2)),
+        (10, (12, 8)),
+        (20, (16, 0)),
+        (50, (16, 9)),
+        (100, (10, 16)),
+        (199, (9, 6)),
+        (200, (8, 2)),
+        (201, (10, 9)),
+        (299, (11, 1)),
+    ] {
+        assert_eq!(laser.nth(&grid, n - 1), Some(pos));
+    }
+    assert_eq!(laser.nth(&grid, 299), None);
+
+    let sweeps = laser.sweeps(&grid);
+    assert_eq!(sweeps[0].len(), grid.visibility((11, 13)));
+    assert_eq!(sweeps.iter().map(Vec::len).sum::<usize>(), 299);
+    assert_eq!(sweeps.last().unwrap(), &[(11, 1)]);
+
+    // turning the other way starts at the same heading, then mirrors the
+    // rest of the first sweep
+    let backwards = Laser {
+        rotation: Rotation::Counterclockwise,
+        ..laser
+    };
+    let first = backwards.sweeps(&grid).remove(0);
+    let mut mirrored = sweeps[0][1..].to_vec();
+    mirrored.reverse();
+    assert_eq!(first[0], sweeps[0][0]);
+    assert_eq!(first[1..], mirrored[..]);
+
+    // pointing right, the nearest asteroid in that direction goes first,
+    // whichever way the laser turns
+    let right = Laser {
+        heading: Direction::between((0, 0), (1, 0)).unwrap(),
+        ..laser
+    };
+    assert_eq!(right.nth(&grid, 0), Some((12, 13)));
+    let right_backwards = Laser {
+        rotation: Rotation::Counterclockwise,
+        ..right
+    };
+    assert_eq!(right_backwards.nth(&grid, 0), Some((12, 13)));
+    assert_eq!(
+        right.sweeps(&grid)[0].last(),
+        sweeps[0].iter().rev().find(|&&(x, y)| x > 11 && y < 13)
+    );
+
+    assert_eq!(part2(&Grid::from_str("#.\n.#")), None);
+}
+
+#[test]
+fn test_render() {
+    let grid = Grid::from_str(
+        ".#..#\n\
+         .....\n\
+         #####\n\
+         ....#\n\
+         ...##",
+    );
+    assert_eq!(
+        render_heatmap(&grid, (3, 4)),
+        ".7..7\n.....\n67775\n....7\n...@7\n"
     );
+    let laser = Laser::new((3, 4));
+    assert_eq!(
+        render_order(&grid, &laser),
+        ". 9 . . 2\n. . . . .\n6 7 8 1 3\n. . . . 4\n. . . @ 5\n"
+    );
+
+    let svg = render_svg(&grid, &laser);
+    assert!(svg.starts_with("<svg xmlns=\"http://www.w3.org/2000/svg\" viewBox=\"0 0 50 50\""));
+    assert_eq!(svg.matches("<circle").count(), 10);
+    // the first sweep hits everything the station can see
+    assert_eq!(svg.matches("<line").count(), 8);
+    assert!(svg.contains("<title>3,4 sees 8</title>"));
+    assert!(svg.contains(">9</text>"));
+    assert!(svg.ends_with("</svg>\n"));
 }
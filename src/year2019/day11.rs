@@ -1,4 +1,8 @@
+use crate::year2019::canvas::Canvas;
 use crate::year2019::intcode::{Computer, Word};
+use crate::year2019::ocr;
+use std::cell::RefCell;
+use std::collections::HashMap;
 
 pub fn run() {
     let code = std::fs::read_to_string("inputs/year2019/day11.txt")
@@ -6,62 +10,549 @@ pub fn run() {
         .unwrap();
 
     println!("year2019 day11 part1 {}", part1(&code));
-    println!("year2019 day11 part2\n{}", part2(&code));
+    println!("year2019 day11 part2 {}", part2(&code));
+
+    let replay = ReplayArgs::parse(std::env::args());
+    for target in &replay.targets {
+        let robot = paint(&code, WHITE);
+        match target {
+            Some(dir) => match write_frames(&robot, dir.as_ref(), replay.every, 8) {
+                Ok(count) => println!("wrote {} frames to {}", count, dir),
+                Err(err) => eprintln!("can't write frames to {}: {}", dir, err),
+            },
+            None => {
+                let result = play(&robot, &mut std::io::stdout(), replay.every, replay.delay);
+                if let Err(err) = result {
+                    eprintln!("can't replay: {}", err);
+                }
+            }
+        }
+    }
 }
 
-type Pos = (isize, isize);
-static DIRS: [Pos; 4] = [(0, 1), (1, 0), (0, -1), (-1, 0)];
+/// Panel coordinates, with y pointing up
+pub type Pos = (isize, isize);
 
-fn paint(code: &[Word], start: Word) -> std::collections::HashMap<Pos, Word> {
-    let mut computer = Computer::with_program(code, &[]);
-    let mut hull = std::collections::HashMap::new();
-    hull.insert((0, 0), start);
+pub const BLACK: Word = 0;
+pub const WHITE: Word = 1;
 
-    let mut pos = (0, 0);
-    let mut dir = 0isize;
-    let mut done = false;
-    while !done {
-        let current: &mut Word = hull.entry(pos).or_insert(0);
-        computer.get_input().push_back(*current);
-        done = computer.exec();
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Heading {
+    Up,
+    Right,
+    Down,
+    Left,
+}
 
-        let turn = computer.get_output().pop().unwrap();
-        let paint = computer.get_output().pop().unwrap();
-        *current = paint;
+impl Heading {
+    const ALL: [Heading; 4] = [Heading::Up, Heading::Right, Heading::Down, Heading::Left];
+
+    pub fn step(self) -> Pos {
+        match self {
+            Heading::Up => (0, 1),
+            Heading::Right => (1, 0),
+            Heading::Down => (0, -1),
+            Heading::Left => (-1, 0),
+        }
+    }
 
-        dir = (4 + dir + if turn == 1 { 1 } else { -1 }) % 4;
-        let (dx, dy) = DIRS[dir as usize];
-        pos.0 += dx;
-        pos.1 += dy;
+    /// Turn clockwise by `quarters` quarter turns, or anticlockwise if negative
+    pub fn turn(self, quarters: isize) -> Heading {
+        Heading::ALL[(self as isize + quarters).rem_euclid(4) as usize]
     }
+}
+
+/// How the robot turns for each instruction it is given
+pub type Turn = fn(Heading, Word) -> Heading;
+
+/// The puzzle's rule: 0 turns left and 1 turns right
+pub fn left_or_right(heading: Heading, instruction: Word) -> Heading {
+    heading.turn(if instruction == 1 { 1 } else { -1 })
+}
+
+/// Each instruction is a number of quarter turns clockwise
+pub fn quarter_turns(heading: Heading, instruction: Word) -> Heading {
+    heading.turn(instruction)
+}
 
-    hull
+/// One panel painted, then the turn and move that follow
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Step {
+    pub pos: Pos,
+    pub color: Word,
+    /// Heading after turning, before moving off the panel
+    pub heading: Heading,
+}
+
+/// Hull and robot as they were part way through painting
+#[derive(Clone, Debug, PartialEq)]
+pub struct Snapshot {
+    /// Number of panels painted so far
+    pub step: usize,
+    pub hull: HashMap<Pos, Word>,
+    pub pos: Pos,
+    pub heading: Heading,
+}
+
+/// Hull painting robot, starting at the origin facing up, which records
+/// everything it paints
+#[derive(Clone, Debug)]
+pub struct Robot {
+    turn: Turn,
+    start: HashMap<Pos, Word>,
+    hull: HashMap<Pos, Word>,
+    pos: Pos,
+    heading: Heading,
+    path: Vec<Step>,
+    /// Indices into `path` of every time each panel was painted
+    history: HashMap<Pos, Vec<usize>>,
+    /// Colour received, waiting for the turn instruction that follows it
+    color: Option<Word>,
+}
+
+impl Robot {
+    pub fn new(turn: Turn) -> Robot {
+        Robot {
+            turn,
+            start: HashMap::new(),
+            hull: HashMap::new(),
+            pos: (0, 0),
+            heading: Heading::Up,
+            path: Vec::new(),
+            history: HashMap::new(),
+            color: None,
+        }
+    }
+
+    /// Colour a panel before the robot starts, without counting it as painted
+    pub fn with_panel(mut self, pos: Pos, color: Word) -> Robot {
+        self.start.insert(pos, color);
+        self.hull.insert(pos, color);
+        self
+    }
+
+    /// Colour of the panel under the robot
+    pub fn camera(&self) -> Word {
+        self.hull.get(&self.pos).cloned().unwrap_or(BLACK)
+    }
+
+    /// Take the next output: a colour to paint, then an instruction to turn
+    /// and move forward
+    pub fn command(&mut self, value: Word) {
+        match self.color.take() {
+            None => self.color = Some(value),
+            Some(color) => {
+                self.hull.insert(self.pos, color);
+                self.heading = (self.turn)(self.heading, value);
+                self.history
+                    .entry(self.pos)
+                    .or_default()
+                    .push(self.path.len());
+                self.path.push(Step {
+                    pos: self.pos,
+                    color,
+                    heading: self.heading,
+                });
+                let (dx, dy) = self.heading.step();
+                self.pos = (self.pos.0 + dx, self.pos.1 + dy);
+            }
+        }
+    }
+
+    /// Let a program steer the robot, returning whether it halted
+    pub fn run(&mut self, code: &[Word]) -> bool {
+        let robot = RefCell::new(self);
+        let camera = || Some(robot.borrow().camera());
+        let motor = |value| robot.borrow_mut().command(value);
+        Computer::with_io(code, camera, motor).exec()
+    }
+
+    pub fn pos(&self) -> Pos {
+        self.pos
+    }
+
+    pub fn heading(&self) -> Heading {
+        self.heading
+    }
+
+    pub fn hull(&self) -> &HashMap<Pos, Word> {
+        &self.hull
+    }
+
+    pub fn path(&self) -> &[Step] {
+        &self.path
+    }
+
+    /// Indices into `path` of each time `pos` was painted
+    pub fn history(&self, pos: Pos) -> &[usize] {
+        self.history.get(&pos).map_or(&[], Vec::as_slice)
+    }
+
+    pub fn painted_at_least_once(&self) -> usize {
+        self.history.len()
+    }
+
+    /// Panels painted exactly `n` times
+    pub fn painted_times(&self, n: usize) -> usize {
+        self.history
+            .values()
+            .filter(|steps| steps.len() == n)
+            .count()
+    }
+
+    /// The state after the first `steps` panels were painted
+    pub fn replay(&self, steps: usize) -> Snapshot {
+        let mut snapshot = self.initial();
+        self.advance(&mut snapshot, steps);
+        snapshot
+    }
+
+    /// Snapshots every `every` steps from the start, ending with the final
+    /// state
+    pub fn frames(&self, every: usize) -> Frames<'_> {
+        Frames {
+            robot: self,
+            next: Some(self.initial()),
+            every: every.max(1),
+        }
+    }
+
+    /// Lowest and highest corners of everywhere the robot went or painted
+    pub fn bounds(&self) -> (Pos, Pos) {
+        let mut points = vec![(0, 0), self.pos];
+        points.extend(self.start.keys().cloned());
+        points.extend(self.path.iter().map(|step| step.pos));
+        let xs = points.iter().map(|&(x, _)| x);
+        let ys = points.iter().map(|&(_, y)| y);
+        (
+            (xs.clone().min().unwrap(), ys.clone().min().unwrap()),
+            (xs.max().unwrap(), ys.max().unwrap()),
+        )
+    }
+
+    fn initial(&self) -> Snapshot {
+        Snapshot {
+            step: 0,
+            hull: self.start.clone(),
+            pos: (0, 0),
+            heading: Heading::Up,
+        }
+    }
+
+    /// Apply recorded steps until `to`, or until the recording runs out
+    fn advance(&self, snapshot: &mut Snapshot, to: usize) {
+        let to = to.min(self.path.len());
+        for step in &self.path[snapshot.step.min(to)..to] {
+            snapshot.hull.insert(step.pos, step.color);
+            let (dx, dy) = step.heading.step();
+            snapshot.pos = (step.pos.0 + dx, step.pos.1 + dy);
+            snapshot.heading = step.heading;
+        }
+        snapshot.step = snapshot.step.max(to);
+    }
+}
+
+pub struct Frames<'a> {
+    robot: &'a Robot,
+    next: Option<Snapshot>,
+    every: usize,
+}
+
+impl Iterator for Frames<'_> {
+    type Item = Snapshot;
+
+    fn next(&mut self) -> Option<Snapshot> {
+        let frame = self.next.take()?;
+        if frame.step < self.robot.path.len() {
+            let mut next = frame.clone();
+            self.robot.advance(&mut next, frame.step + self.every);
+            self.next = Some(next);
+        }
+        Some(frame)
+    }
+}
+
+impl Snapshot {
+    /// The hull between two corners, top row first: `#` for white, `.` for
+    /// panels painted black, and an arrow for the robot
+    pub fn to_text(&self, (min, max): (Pos, Pos)) -> String {
+        let mut output = String::new();
+        for y in (min.1..=max.1).rev() {
+            for x in min.0..=max.0 {
+                output.push(if (x, y) == self.pos {
+                    match self.heading {
+                        Heading::Up => '^',
+                        Heading::Right => '>',
+                        Heading::Down => 'v',
+                        Heading::Left => '<',
+                    }
+                } else {
+                    match self.hull.get(&(x, y)) {
+                        Some(&WHITE) => '#',
+                        Some(_) => '.',
+                        None => ' ',
+                    }
+                });
+            }
+            output.push('\n');
+        }
+        output
+    }
+
+    /// Plain PPM with `scale` pixels per panel. The robot is red, with a
+    /// yellow edge on the side it faces.
+    pub fn to_ppm(&self, (min, max): (Pos, Pos), scale: usize) -> String {
+        let width = (max.0 - min.0 + 1) as usize * scale;
+        let height = (max.1 - min.1 + 1) as usize * scale;
+        let mut ppm = format!("P3\n{} {}\n255\n", width, height);
+        for py in 0..height {
+            let row = (0..width)
+                .map(|px| {
+                    let pos = (min.0 + (px / scale) as isize, max.1 - (py / scale) as isize);
+                    let (cx, cy) = (px % scale, py % scale);
+                    let nose = match self.heading {
+                        Heading::Up => cy == 0,
+                        Heading::Right => cx == scale - 1,
+                        Heading::Down => cy == scale - 1,
+                        Heading::Left => cx == 0,
+                    };
+                    if pos == self.pos {
+                        if nose {
+                            "255 220 0"
+                        } else {
+                            "220 0 0"
+                        }
+                    } else {
+                        match self.hull.get(&pos) {
+                            Some(&WHITE) => "255 255 255",
+                            Some(_) => "64 64 64",
+                            None => "0 0 0",
+                        }
+                    }
+                })
+                .collect::<Vec<_>>();
+            ppm.push_str(&row.join(" "));
+            ppm.push('\n');
+        }
+        ppm
+    }
+}
+
+/// Redraw the terminal for each frame, waiting `delay` between them
+pub fn play<W: std::io::Write>(
+    robot: &Robot,
+    out: &mut W,
+    every: usize,
+    delay: std::time::Duration,
+) -> std::io::Result<()> {
+    let bounds = robot.bounds();
+    for frame in robot.frames(every) {
+        // move home and clear before drawing over the last frame
+        write!(out, "\x1b[H\x1b[2J")?;
+        write!(out, "{}", frame.to_text(bounds))?;
+        writeln!(out, "step {}/{}", frame.step, robot.path().len())?;
+        out.flush()?;
+        std::thread::sleep(delay);
+    }
+    Ok(())
+}
+
+/// Write frames to `dir` as `frame00000.ppm` onwards, creating `dir` if
+/// needed, and return how many were written
+pub fn write_frames(
+    robot: &Robot,
+    dir: &std::path::Path,
+    every: usize,
+    scale: usize,
+) -> std::io::Result<usize> {
+    std::fs::create_dir_all(dir)?;
+    let bounds = robot.bounds();
+    let mut count = 0;
+    for frame in robot.frames(every) {
+        let path = dir.join(format!("frame{:05}.ppm", count));
+        std::fs::write(path, frame.to_ppm(bounds, scale))?;
+        count += 1;
+    }
+    Ok(count)
+}
+
+/// Replays asked for on the command line: `--replay` watches part 2 in the
+/// terminal and `--replay=DIR` saves its frames, one frame per
+/// `--replay-every=N` steps (10 by default) shown for `--replay-delay=MS`
+/// milliseconds (30 by default)
+#[derive(Debug, PartialEq)]
+struct ReplayArgs {
+    /// A directory to write frames to, or `None` for the terminal
+    targets: Vec<Option<String>>,
+    every: usize,
+    delay: std::time::Duration,
+}
+
+impl ReplayArgs {
+    fn parse<I: IntoIterator<Item = String>>(args: I) -> ReplayArgs {
+        let mut replay = ReplayArgs {
+            targets: Vec::new(),
+            every: 10,
+            delay: std::time::Duration::from_millis(30),
+        };
+        for arg in args {
+            if arg == "--replay" {
+                replay.targets.push(None);
+            } else if let Some(dir) = arg.strip_prefix("--replay=") {
+                replay.targets.push(Some(dir.to_owned()));
+            } else if let Some(steps) = arg.strip_prefix("--replay-every=") {
+                match steps.parse() {
+                    Ok(steps) => replay.every = steps,
+                    Err(_) => eprintln!("ignoring {}: expected a number of steps", arg),
+                }
+            } else if let Some(ms) = arg.strip_prefix("--replay-delay=") {
+                match ms.parse() {
+                    Ok(ms) => replay.delay = std::time::Duration::from_millis(ms),
+                    Err(_) => eprintln!("ignoring {}: expected milliseconds", arg),
+                }
+            }
+        }
+        replay
+    }
+}
+
+fn paint(code: &[Word], start: Word) -> Robot {
+    let mut robot = Robot::new(left_or_right).with_panel((0, 0), start);
+    assert!(robot.run(code));
+    robot
 }
 
 fn part1(code: &[Word]) -> usize {
-    let hull = paint(code, 0);
-    hull.len()
+    paint(code, BLACK).painted_at_least_once()
 }
 
+/// The registration identifier, or the painted hull if it can't be read
 fn part2(code: &[Word]) -> String {
-    let hull = paint(code, 1);
-
-    let min_x = *hull.keys().map(|(x, _)| x).min().unwrap();
-    let max_x = *hull.keys().map(|(x, _)| x).max().unwrap();
-    let min_y = *hull.keys().map(|(_, y)| y).min().unwrap();
-    let max_y = *hull.keys().map(|(_, y)| y).max().unwrap();
-
-    let mut output = String::new();
-    for y in (min_y..(max_y + 1)).rev() {
-        for x in min_x..(max_x + 1) {
-            output.push(if hull.get(&(x, y)).cloned().unwrap_or(0) == 1 {
-                'X'
-            } else {
-                ' '
-            })
-        }
-        output.push('\n')
+    let robot = paint(code, WHITE);
+    let white = robot
+        .hull()
+        .iter()
+        .filter(|&(_, &color)| color == WHITE)
+        .map(|(&pos, _)| pos);
+    // the hull has y pointing up
+    let canvas = Canvas::from_points(white).flip_vertical();
+    ocr::recognize(&canvas).unwrap_or_else(|err| format!("{}\n{}", err, canvas.to_text('X', ' ')))
+}
+
+#[cfg(test)]
+fn drive(robot: &mut Robot, outputs: &[Word]) {
+    outputs.iter().for_each(|&value| robot.command(value));
+}
+
+#[test]
+fn test_robot() {
+    // the moves from the puzzle's example
+    let mut robot = Robot::new(left_or_right);
+    drive(&mut robot, &[1, 0, 0, 0, 1, 0, 1, 0, 0, 1, 1, 0, 1, 0]);
+    assert_eq!(robot.pos(), (0, 1));
+    assert_eq!(robot.heading(), Heading::Left);
+    assert_eq!(robot.path().len(), 7);
+    assert_eq!(robot.painted_at_least_once(), 6);
+    assert_eq!(robot.painted_times(1), 5);
+    assert_eq!(robot.painted_times(2), 1);
+    assert_eq!(robot.history((0, 0)), &[0, 4]);
+    assert_eq!(robot.history((5, 5)), &[] as &[usize]);
+    assert_eq!(robot.camera(), BLACK);
+
+    let halfway = robot.replay(4);
+    assert_eq!(halfway.pos, (0, 0));
+    assert_eq!(halfway.heading, Heading::Up);
+    // the second coat on the origin comes at step 4
+    assert_eq!(halfway.hull[&(0, 0)], WHITE);
+    assert_eq!(robot.replay(0).hull, HashMap::new());
+    assert_eq!(robot.replay(100).hull, *robot.hull());
+
+    let mut robot = Robot::new(quarter_turns).with_panel((0, 0), WHITE);
+    drive(&mut robot, &[1, 2, 0, -1]);
+    assert_eq!(robot.heading(), Heading::Right);
+    assert_eq!(robot.pos(), (1, -1));
+    assert_eq!(robot.painted_at_least_once(), 2);
+    assert_eq!(robot.replay(0).hull[&(0, 0)], WHITE);
+}
+
+#[test]
+fn test_frames() {
+    let mut robot = Robot::new(left_or_right);
+    drive(&mut robot, &[1, 0, 0, 0, 1, 0, 1, 0, 0, 1, 1, 0, 1, 0]);
+    let bounds = robot.bounds();
+    assert_eq!(bounds, ((-1, -1), (1, 1)));
+
+    let frames = robot.frames(3).collect::<Vec<_>>();
+    assert_eq!(
+        frames.iter().map(|frame| frame.step).collect::<Vec<_>>(),
+        vec![0, 3, 6, 7]
+    );
+    for frame in &frames {
+        assert_eq!(*frame, robot.replay(frame.step));
     }
+    assert_eq!(frames[0].to_text(bounds), "   \n ^ \n   \n");
+    assert_eq!(frames[3].to_text(bounds), " <#\n..#\n## \n");
+
+    let ppm = frames[0].to_ppm(bounds, 2);
+    let lines = ppm.lines().collect::<Vec<_>>();
+    assert_eq!(lines[..3], ["P3", "6 6", "255"]);
+    // the robot's top edge is its nose, as it faces up
+    assert!(lines[5].starts_with("0 0 0 0 0 0 255 220 0 255 220 0 0 0 0"));
+    assert!(lines[6].starts_with("0 0 0 0 0 0 220 0 0 220 0 0 0 0 0"));
+
+    let mut out = Vec::new();
+    play(&robot, &mut out, 100, std::time::Duration::ZERO).unwrap();
+    let out = String::from_utf8(out).unwrap();
+    assert_eq!(out.matches("\x1b[H\x1b[2J").count(), 2);
+    assert!(out.ends_with("step 7/7\n"));
 
-    output
+    // the output directory doesn't have to exist yet
+    let root = std::env::temp_dir().join(format!("day11-frames-{}", std::process::id()));
+    let dir = root.join("nested");
+    assert_eq!(write_frames(&robot, &dir, 3, 1).unwrap(), 4);
+    let last = std::fs::read_to_string(dir.join("frame00003.ppm")).unwrap();
+    assert_eq!(last, frames[3].to_ppm(bounds, 1));
+    std::fs::remove_dir_all(root).unwrap();
+}
+
+#[test]
+fn test_run() {
+    // paint white and turn right four times, then halt
+    let code = [
+        3, 100, 104, 1, 104, 1, 1001, 101, 1, 101, 1008, 101, 4, 102, 1006, 102, 0, 99,
+    ];
+    let mut robot = Robot::new(left_or_right);
+    assert!(robot.run(&code));
+    assert_eq!(robot.path().len(), 4);
+    assert_eq!(robot.pos(), (0, 0));
+    assert_eq!(robot.camera(), WHITE);
+    assert_eq!(robot.painted_times(1), 4);
+}
+
+#[test]
+fn test_replay_args() {
+    let args = |args: &[&str]| ReplayArgs::parse(args.iter().map(|arg| arg.to_string()));
+    assert_eq!(
+        args(&["aoc", "--replayfoo", "--verbose"]),
+        ReplayArgs {
+            targets: vec![],
+            every: 10,
+            delay: std::time::Duration::from_millis(30),
+        }
+    );
+    assert_eq!(
+        args(&[
+            "aoc",
+            "--replay",
+            "--replay-every=3",
+            "--replay=frames",
+            "--replay-delay=5",
+            "--replay-delay=soon",
+        ]),
+        ReplayArgs {
+            targets: vec![None, Some("frames".to_owned())],
+            every: 3,
+            delay: std::time::Duration::from_millis(5),
+        }
+    );
 }
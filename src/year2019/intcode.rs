@@ -1,14 +1,37 @@
 use std::collections::VecDeque;
+use std::sync::Arc;
+
+pub mod aot;
+pub mod compile;
+pub mod futures;
+mod io;
+pub mod isa;
+pub mod threads;
+
+pub use io::{Input, IterInput, Output, ReadInput, WriteOutput};
+pub use isa::InstructionSet;
 
 pub type Word = isize;
 
 #[derive(Clone, Debug)]
-pub struct Computer {
+pub struct Computer<I = VecDeque<Word>, O = Vec<Word>> {
     data: Vec<Word>,
-    input: VecDeque<Word>,
-    output: Vec<Word>,
+    input: I,
+    output: O,
     pc: Word,
     rel_base: Word,
+    isa: Arc<InstructionSet>,
+    exit_code: Option<Word>,
+}
+
+/// Why `Computer::run` returned
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Status {
+    Halted,
+    /// Stopped at an `In` instruction with no input available
+    Waiting,
+    /// A custom instruction asked to pause
+    Break,
 }
 
 impl Computer {
@@ -17,61 +40,107 @@ impl Computer {
         input
             .as_ref()
             .trim()
-            .split(",")
+            .split(',')
             .map(Word::from_str)
             .collect::<Result<Vec<Word>, _>>()
             .unwrap()
     }
 
     pub fn new() -> Self {
-        Self {
-            data: Vec::new(),
-            input: VecDeque::new(),
-            output: Vec::new(),
-            pc: 0,
-            rel_base: 0,
-        }
+        Self::with_io(&[], VecDeque::new(), Vec::new())
     }
 
     pub fn with_program(code: &[Word], input: &[Word]) -> Self {
-        let mut program = Computer::new();
-        program.reset(code);
-        program.set_input(input);
-        program
+        Self::with_io(code, input.iter().cloned().collect(), Vec::new())
     }
 
     pub fn run_program(code: &[Word], input: &[Word]) -> Vec<Word> {
-        let mut program = Computer::new();
-        program.reset(code);
-        program.set_input(input);
+        let mut program = Computer::with_program(code, input);
         let done = program.exec();
         assert!(done);
         program.output
     }
 
+    pub fn set_input(&mut self, input: &[Word]) {
+        self.input = input.iter().cloned().collect();
+    }
+}
+
+impl Default for Computer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<I: Input, O: Output> Computer<I, O> {
+    /// Load `code` with custom input and output handling
+    pub fn with_io(code: &[Word], input: I, output: O) -> Self {
+        Self {
+            data: code.to_vec(),
+            input,
+            output,
+            pc: 0,
+            rel_base: 0,
+            isa: Arc::default(),
+            exit_code: None,
+        }
+    }
+
+    /// Reload memory and registers and clear any kept output; input is left
+    /// untouched
     pub fn reset(&mut self, code: &[Word]) {
         self.data.clear();
         self.data.extend_from_slice(code);
-        self.output.clear();
+        self.output.clear_output();
         self.pc = 0;
         self.rel_base = 0;
+        self.exit_code = None;
     }
 
-    pub fn set_input(&mut self, input: &[Word]) {
-        self.input = input.iter().cloned().collect();
+    /// Enable custom instructions on top of the AoC 2019 ones
+    pub fn set_instruction_set(&mut self, isa: Arc<InstructionSet>) {
+        self.isa = isa;
     }
 
-    pub fn get_input(&mut self) -> &mut VecDeque<Word> {
+    /// Set when a custom instruction halts the machine with a code
+    pub fn exit_code(&self) -> Option<Word> {
+        self.exit_code
+    }
+
+    pub fn get_input(&mut self) -> &mut I {
         &mut self.input
     }
 
-    pub fn get_output(&mut self) -> &mut Vec<Word> {
+    pub fn get_output(&mut self) -> &mut O {
         &mut self.output
     }
 
+    /// Give back the input and output, e.g. to recover a log after halting
+    pub fn into_io(self) -> (I, O) {
+        (self.input, self.output)
+    }
+
+    /// Run until halting or waiting for input, stepping over breaks from
+    /// custom instructions. Returns true if halted.
     pub fn exec(&mut self) -> bool {
         loop {
-            let opcode = self.get_opcode();
+            match self.run() {
+                Status::Halted => return true,
+                Status::Waiting => return false,
+                Status::Break => continue,
+            }
+        }
+    }
+
+    pub fn run(&mut self) -> Status {
+        loop {
+            let opcode = match self.get_opcode() {
+                Ok(opcode) => opcode,
+                Err(custom) => match self.run_custom(custom) {
+                    Some(status) => return status,
+                    None => continue,
+                },
+            };
             match opcode {
                 Opcode::Add => {
                     *self.param_out(3) = self.param_in(1) + self.param_in(2);
@@ -79,16 +148,14 @@ impl Computer {
                 Opcode::Mul => {
                     *self.param_out(3) = self.param_in(1) * self.param_in(2);
                 }
-                Opcode::In => {
-                    if self.input.is_empty() {
-                        // yield until more input
-                        return false;
-                    }
-                    *self.param_out(1) = self.input.pop_front().unwrap()
-                }
+                Opcode::In => match self.input.next_input() {
+                    Some(val) => *self.param_out(1) = val,
+                    // yield until more input
+                    None => return Status::Waiting,
+                },
                 Opcode::Out => {
                     let val = self.param_in(1);
-                    self.output.push(val)
+                    self.output.push_output(val)
                 }
                 Opcode::JmpT => {
                     if self.param_in(1) != 0 {
@@ -115,11 +182,32 @@ impl Computer {
             self.pc += opcode.len();
 
             if opcode == Opcode::End {
-                return true;
+                return Status::Halted;
             }
         }
     }
 
+    fn run_custom(&mut self, opcode: Word) -> Option<Status> {
+        let instruction = match self.isa.get(opcode) {
+            Some(instruction) => instruction,
+            None => panic!("invalid opcode: {}", opcode),
+        };
+        match (instruction.handler)(self) {
+            isa::Flow::Next => self.pc += instruction.len,
+            isa::Flow::Jump(addr) => self.pc = addr,
+            isa::Flow::Break => {
+                self.pc += instruction.len;
+                return Some(Status::Break);
+            }
+            isa::Flow::Halt(code) => {
+                self.pc += instruction.len;
+                self.exit_code = Some(code);
+                return Some(Status::Halted);
+            }
+        }
+        None
+    }
+
     fn read(&mut self, addr: Word) -> Word {
         let addr = addr as usize;
         if addr >= self.data.len() {
@@ -128,8 +216,18 @@ impl Computer {
         self.data[addr]
     }
 
-    fn get_opcode(&self) -> Opcode {
-        get_digits_base10(self.data[self.pc as usize], 0, 2).into()
+    fn write(&mut self, addr: Word, value: Word) {
+        let addr = addr as usize;
+        if addr >= self.data.len() {
+            self.data.resize(addr + 1, 0)
+        }
+        self.data[addr] = value;
+    }
+
+    /// The built-in instruction at `pc`, or the opcode to look up as a custom one
+    fn get_opcode(&self) -> Result<Opcode, Word> {
+        let opcode = get_digits_base10(self.data[self.pc as usize], 0, 2);
+        Opcode::decode(opcode).ok_or(opcode)
     }
 
     fn get_param_mode(&self, offset: u32) -> Param {
@@ -162,6 +260,28 @@ impl Computer {
     }
 }
 
+impl<I: Input, O: Output> isa::Context for Computer<I, O> {
+    fn param(&mut self, n: u32) -> Word {
+        self.param_in(n)
+    }
+
+    fn set_param(&mut self, n: u32, value: Word) {
+        *self.param_out(n) = value
+    }
+
+    fn pc(&self) -> Word {
+        self.pc
+    }
+
+    fn input(&mut self) -> Option<Word> {
+        self.input.next_input()
+    }
+
+    fn output(&mut self, value: Word) {
+        self.output.push_output(value)
+    }
+}
+
 #[derive(Clone, Copy, Debug, PartialEq)]
 enum Param {
     Addr,
@@ -207,21 +327,21 @@ impl Opcode {
     }
 }
 
-impl From<Word> for Opcode {
-    fn from(word: Word) -> Opcode {
-        match word {
-            01 => Opcode::Add,
-            02 => Opcode::Mul,
-            03 => Opcode::In,
-            04 => Opcode::Out,
-            05 => Opcode::JmpT,
-            06 => Opcode::JmpF,
-            07 => Opcode::Lt,
-            08 => Opcode::Eq,
-            09 => Opcode::SetRel,
+impl Opcode {
+    fn decode(word: Word) -> Option<Opcode> {
+        Some(match word {
+            1 => Opcode::Add,
+            2 => Opcode::Mul,
+            3 => Opcode::In,
+            4 => Opcode::Out,
+            5 => Opcode::JmpT,
+            6 => Opcode::JmpF,
+            7 => Opcode::Lt,
+            8 => Opcode::Eq,
+            9 => Opcode::SetRel,
             99 => Opcode::End,
-            op => panic!("invalid opcode: {}", op),
-        }
+            _ => return None,
+        })
     }
 }
 
@@ -240,3 +360,31 @@ fn test_get_digits_base10() {
     assert_eq!(get_digits_base10(12345, 4, 2), 1);
     assert_eq!(get_digits_base10(12345, 5, 1), 0);
 }
+
+#[test]
+fn test_custom_io() {
+    // doubles a single input
+    let code = &[3, 9, 1002, 9, 2, 9, 4, 9, 99, 0];
+
+    let mut seen = Vec::new();
+    let input = IterInput(vec![21].into_iter());
+    assert!(Computer::with_io(code, input, |v| seen.push(v)).exec());
+    assert_eq!(seen, vec![42]);
+
+    let ready = std::cell::Cell::new(false);
+    let input = || if ready.get() { Some(5) } else { None };
+    let mut computer = Computer::with_io(code, input, Vec::new());
+    assert!(!computer.exec());
+    ready.set(true);
+    assert!(computer.exec());
+    assert_eq!(*computer.get_output(), vec![10]);
+    computer.reset(code);
+    assert!(computer.get_output().is_empty());
+    assert!(computer.exec());
+    assert_eq!(computer.into_io().1, vec![10]);
+
+    let mut out = Vec::new();
+    let input = ReadInput::new("7\n".as_bytes());
+    assert!(Computer::with_io(code, input, WriteOutput::new(&mut out)).exec());
+    assert_eq!(out, b"14\n");
+}
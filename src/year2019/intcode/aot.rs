@@ -0,0 +1,109 @@
+use super::{Computer, Input, Output, Word};
+use std::collections::VecDeque;
+
+/// How a compiled program handed control back
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Exit {
+    Halted,
+    /// Stopped at an `In` instruction with no input available
+    Waiting,
+    /// Left the translated code, or wrote over it, so only the interpreter can continue
+    Fallback,
+}
+
+pub type Program<I, O> = fn(&mut Computer<I, O>) -> Exit;
+
+/// Programs translated by `build.rs` from the puzzle inputs. Each is only valid
+/// for the exact code it was generated from.
+#[allow(clippy::all, unused_parens)]
+mod programs {
+    use super::{Computer, Exit, Input, Output, Word};
+
+    include!(concat!(env!("OUT_DIR"), "/intcode_aot.rs"));
+}
+
+pub use programs::*;
+
+/// Runs a machine with its compiled program, switching to the interpreter for
+/// good once the program falls back
+pub struct Compiled<I, O> {
+    computer: Computer<I, O>,
+    program: Option<Program<I, O>>,
+}
+
+impl<I: Input, O: Output> Compiled<I, O> {
+    pub fn new(computer: Computer<I, O>, program: Program<I, O>) -> Self {
+        Self {
+            computer,
+            program: Some(program),
+        }
+    }
+
+    /// Same contract as `Computer::exec`
+    pub fn exec(&mut self) -> bool {
+        if let Some(program) = self.program {
+            match program(&mut self.computer) {
+                Exit::Halted => return true,
+                Exit::Waiting => return false,
+                Exit::Fallback => self.program = None,
+            }
+        }
+        self.computer.exec()
+    }
+
+    pub fn computer(&mut self) -> &mut Computer<I, O> {
+        &mut self.computer
+    }
+}
+
+pub fn run_compiled(
+    program: Program<VecDeque<Word>, Vec<Word>>,
+    code: &[Word],
+    input: &[Word],
+) -> Vec<Word> {
+    let mut compiled = Compiled::new(Computer::with_program(code, input), program);
+    let done = compiled.exec();
+    assert!(done);
+    compiled.computer.output
+}
+
+#[test]
+fn test_run_compiled() {
+    fn load(day: &str) -> Vec<Word> {
+        std::fs::read_to_string(format!("inputs/year2019/{}.txt", day))
+            .map(Computer::decode)
+            .unwrap()
+    }
+
+    let code = load("day05");
+    for input in &[[1], [5]] {
+        assert_eq!(
+            run_compiled(day05, &code, input),
+            Computer::run_program(&code, input)
+        );
+    }
+
+    let code = load("day07");
+    for phase in 0..5 {
+        let input = [phase, 3 * phase + 7];
+        assert_eq!(
+            run_compiled(day07, &code, &input),
+            Computer::run_program(&code, &input)
+        );
+    }
+
+    let code = load("day09");
+    for input in &[[1], [2]] {
+        assert_eq!(
+            run_compiled(day09, &code, input),
+            Computer::run_program(&code, input)
+        );
+    }
+
+    // rewrites the operand of its own output instruction
+    let code = [1101, 40, 2, 5, 104, 5, 99];
+    let mut computer = Computer::with_program(&code, &[]);
+    assert_eq!(patched(&mut computer), Exit::Fallback);
+    assert_eq!(computer.pc, 4);
+    assert_eq!(run_compiled(patched, &code, &[]), vec![42]);
+}
@@ -0,0 +1,247 @@
+//! Ahead-of-time translation of Intcode programs into Rust source.
+//!
+//! This file must not depend on the rest of the crate, since `build.rs`
+//! includes it directly to generate the programs in `intcode::aot`.
+
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+type Word = isize;
+
+pub fn decode(input: &str) -> Vec<Word> {
+    input
+        .trim()
+        .split(',')
+        .map(|word| word.parse().unwrap())
+        .collect()
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Op {
+    Add,
+    Mul,
+    In,
+    Out,
+    JmpT,
+    JmpF,
+    Lt,
+    Eq,
+    SetRel,
+    End,
+}
+
+#[derive(Clone, Copy, Debug)]
+struct Instr {
+    op: Op,
+    params: [(Word, Word); 3],
+}
+
+impl Instr {
+    fn decode(code: &[Word], pc: usize) -> Option<Instr> {
+        let word = *code.get(pc)?;
+        if word < 0 {
+            return None;
+        }
+        let op = match word % 100 {
+            1 => Op::Add,
+            2 => Op::Mul,
+            3 => Op::In,
+            4 => Op::Out,
+            5 => Op::JmpT,
+            6 => Op::JmpF,
+            7 => Op::Lt,
+            8 => Op::Eq,
+            9 => Op::SetRel,
+            99 => Op::End,
+            _ => return None,
+        };
+        let mut params = [(0, 0); 3];
+        let mut modes = word / 100;
+        for (i, param) in params.iter_mut().enumerate().take(Instr::len(op) - 1) {
+            if modes % 10 > 2 {
+                return None;
+            }
+            *param = (modes % 10, *code.get(pc + 1 + i)?);
+            modes /= 10;
+        }
+        Some(Instr { op, params })
+    }
+
+    fn len(op: Op) -> usize {
+        match op {
+            Op::Add | Op::Mul | Op::Lt | Op::Eq => 4,
+            Op::JmpT | Op::JmpF => 3,
+            Op::In | Op::Out | Op::SetRel => 2,
+            Op::End => 1,
+        }
+    }
+
+    fn load(&self, n: usize) -> String {
+        match self.params[n] {
+            (0, addr) => format!("vm.read({})", addr),
+            (1, value) => format!("({})", value),
+            (_, offset) => format!("vm.read(vm.rel_base + ({}))", offset),
+        }
+    }
+
+    /// `None` for immediate destinations, which the interpreter rejects
+    fn address(&self, n: usize) -> Option<String> {
+        match self.params[n] {
+            (0, addr) => Some(format!("{}", addr)),
+            (1, _) => None,
+            (_, offset) => Some(format!("vm.rel_base + ({})", offset)),
+        }
+    }
+}
+
+/// Follow fall-through and immediate jump targets from the entry point. Returns
+/// are usually computed jumps, but land just after the jump making the call,
+/// so walking past unconditional jumps keeps most of the program reachable.
+fn reachable(code: &[Word]) -> BTreeMap<usize, Instr> {
+    let mut found = BTreeMap::new();
+    let mut pending = vec![0];
+    while let Some(pc) = pending.pop() {
+        if found.contains_key(&pc) {
+            continue;
+        }
+        let instr = match Instr::decode(code, pc) {
+            Some(instr) => instr,
+            None => continue,
+        };
+        found.insert(pc, instr);
+        if instr.op == Op::End {
+            continue;
+        }
+        pending.push(pc + Instr::len(instr.op));
+        if let (Op::JmpT, (1, target)) | (Op::JmpF, (1, target)) = (instr.op, instr.params[1]) {
+            if target >= 0 {
+                pending.push(target as usize);
+            }
+        }
+    }
+    found
+}
+
+/// Generate `pub fn name(vm: &mut Computer<I, O>) -> Exit` running `code` with
+/// every operand mode resolved at translation time. It resumes from `vm.pc`,
+/// and returns `Exit::Fallback` with the machine state intact whenever it
+/// reaches an address it did not translate or writes over translated code.
+pub fn translate(name: &str, code: &[Word]) -> String {
+    let instrs = reachable(code);
+
+    let mut cells = vec![false; code.len()];
+    for (&pc, instr) in &instrs {
+        cells[pc..pc + Instr::len(instr.op)]
+            .iter_mut()
+            .for_each(|cell| *cell = true);
+    }
+    let mut ranges = Vec::new();
+    let mut start = None;
+    for (addr, &cell) in cells.iter().chain(Some(&false)).enumerate() {
+        match (cell, start) {
+            (true, None) => start = Some(addr),
+            (false, Some(first)) => {
+                ranges.push(format!("{}..={}", first, addr - 1));
+                start = None;
+            }
+            _ => {}
+        }
+    }
+    let is_code = if ranges.is_empty() {
+        "false".to_owned()
+    } else {
+        format!("matches!(addr, {})", ranges.join(" | "))
+    };
+
+    let mut out = String::new();
+    writeln!(
+        out,
+        "pub fn {}<I: Input, O: Output>(vm: &mut Computer<I, O>) -> Exit {{",
+        name
+    )
+    .unwrap();
+    writeln!(out, "    fn is_code(addr: Word) -> bool {{").unwrap();
+    writeln!(out, "        {}", is_code).unwrap();
+    writeln!(out, "    }}").unwrap();
+    writeln!(out, "    loop {{").unwrap();
+    writeln!(out, "        match vm.pc {{").unwrap();
+    for (&pc, instr) in &instrs {
+        let next = pc + Instr::len(instr.op);
+        writeln!(out, "            {} => {{", pc).unwrap();
+        let body = match instr.op {
+            Op::Add | Op::Mul | Op::Lt | Op::Eq => {
+                let value = match instr.op {
+                    Op::Add => format!("{} + {}", instr.load(0), instr.load(1)),
+                    Op::Mul => format!("{} * {}", instr.load(0), instr.load(1)),
+                    Op::Lt => format!("({} < {}) as Word", instr.load(0), instr.load(1)),
+                    _ => format!("({} == {}) as Word", instr.load(0), instr.load(1)),
+                };
+                store(instr.address(2), &value, next)
+            }
+            Op::In => match instr.address(0) {
+                Some(addr) => format!(
+                    "let value = match vm.input.next_input() {{\n\
+                     Some(value) => value,\n\
+                     None => return Exit::Waiting,\n\
+                     }};\n\
+                     {}",
+                    store(Some(addr), "value", next)
+                ),
+                None => store(None, "", next),
+            },
+            Op::Out => format!(
+                "let value = {};\nvm.output.push_output(value);\nvm.pc = {};",
+                instr.load(0),
+                next
+            ),
+            Op::JmpT | Op::JmpF => format!(
+                "vm.pc = if {} {} 0 {{ {} }} else {{ {} }};",
+                instr.load(0),
+                if instr.op == Op::JmpT { "!=" } else { "==" },
+                instr.load(1),
+                next
+            ),
+            Op::SetRel => format!("vm.rel_base += {};\nvm.pc = {};", instr.load(0), next),
+            Op::End => format!("vm.pc = {};\nreturn Exit::Halted;", next),
+        };
+        for line in body.lines() {
+            writeln!(out, "                {}", line).unwrap();
+        }
+        writeln!(out, "            }}").unwrap();
+    }
+    writeln!(out, "            _ => return Exit::Fallback,").unwrap();
+    writeln!(out, "        }}").unwrap();
+    writeln!(out, "    }}").unwrap();
+    writeln!(out, "}}").unwrap();
+    out
+}
+
+fn store(addr: Option<String>, value: &str, next: usize) -> String {
+    match addr {
+        Some(addr) => format!(
+            "let value = {};\n\
+             let addr = {};\n\
+             vm.write(addr, value);\n\
+             vm.pc = {};\n\
+             if is_code(addr) {{\n\
+             return Exit::Fallback;\n\
+             }}",
+            value, addr, next
+        ),
+        // leave the interpreter to report the invalid destination
+        None => "return Exit::Fallback;".to_owned(),
+    }
+}
+
+#[test]
+fn test_reachable() {
+    // jumps over a data word, with the halt unreachable except by fall-through
+    let code = [1105, 1, 4, 7, 104, 3, 99];
+    let found = reachable(&code);
+    assert_eq!(found.keys().cloned().collect::<Vec<_>>(), vec![0, 3, 4, 6]);
+    assert_eq!(found[&3].op, Op::Lt);
+
+    let source = translate("example", &code);
+    assert!(source.contains("matches!(addr, 0..=6)"));
+    assert!(source.contains("vm.output.push_output(value);"));
+}
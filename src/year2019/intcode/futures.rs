@@ -0,0 +1,209 @@
+use super::{Computer, Input, Output, Status, Word};
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::future::{poll_fn, Future};
+use std::pin::Pin;
+use std::rc::Rc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::task::{Context, Poll, Wake, Waker};
+
+/// Single-threaded queue connecting async machines to each other and to other tasks
+#[derive(Clone, Default)]
+pub struct Pipe(Rc<RefCell<PipeState>>);
+
+#[derive(Default)]
+struct PipeState {
+    queue: VecDeque<Word>,
+    closed: bool,
+    waiters: Vec<Waker>,
+}
+
+impl Pipe {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn send(&self, value: Word) {
+        let mut state = self.0.borrow_mut();
+        state.queue.push_back(value);
+        state.waiters.drain(..).for_each(Waker::wake);
+    }
+
+    /// Let receivers finish once the queue drains
+    pub fn close(&self) {
+        let mut state = self.0.borrow_mut();
+        state.closed = true;
+        state.waiters.drain(..).for_each(Waker::wake);
+    }
+
+    pub fn try_recv(&self) -> Option<Word> {
+        self.0.borrow_mut().queue.pop_front()
+    }
+
+    /// Wait for the next value, or `None` once the pipe is closed and empty
+    pub async fn recv(&self) -> Option<Word> {
+        poll_fn(|cx| self.poll_next(cx, true)).await
+    }
+
+    /// Wait until a value can be taken without removing it
+    async fn readable(&self) -> bool {
+        poll_fn(|cx| self.poll_next(cx, false)).await.is_some()
+    }
+
+    fn poll_next(&self, cx: &mut Context, take: bool) -> Poll<Option<Word>> {
+        let mut state = self.0.borrow_mut();
+        let next = if take {
+            state.queue.pop_front()
+        } else {
+            state.queue.front().cloned()
+        };
+        if next.is_some() || state.closed {
+            Poll::Ready(next)
+        } else {
+            state.waiters.push(cx.waker().clone());
+            Poll::Pending
+        }
+    }
+}
+
+impl Input for Pipe {
+    fn next_input(&mut self) -> Option<Word> {
+        self.try_recv()
+    }
+}
+
+impl Output for Pipe {
+    fn push_output(&mut self, value: Word) {
+        self.send(value)
+    }
+}
+
+impl<O: Output> Computer<Pipe, O> {
+    /// Run until halting, suspending whenever the input pipe is empty.
+    /// Returns false if the input pipe was closed before the machine halted.
+    pub async fn run_async(&mut self) -> bool {
+        loop {
+            match self.run() {
+                Status::Halted => return true,
+                Status::Waiting => {
+                    if !self.input.readable().await {
+                        return false;
+                    }
+                }
+                // nobody is waiting on a break here, so carry on
+                Status::Break => {}
+            }
+        }
+    }
+}
+
+struct Flag(AtomicBool);
+
+impl Wake for Flag {
+    fn wake(self: Arc<Self>) {
+        self.0.store(true, Ordering::SeqCst)
+    }
+}
+
+struct Task<'a> {
+    future: Pin<Box<dyn Future<Output = ()> + 'a>>,
+    woken: Arc<Flag>,
+}
+
+/// Minimal single-threaded executor that polls spawned futures as they are woken
+#[derive(Default)]
+pub struct Executor<'a> {
+    tasks: Vec<Task<'a>>,
+}
+
+impl<'a> Executor<'a> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn spawn(&mut self, future: impl Future<Output = ()> + 'a) {
+        self.tasks.push(Task {
+            future: Box::pin(future),
+            woken: Arc::new(Flag(AtomicBool::new(true))),
+        });
+    }
+
+    /// Drive every task to completion. Returns false if the remaining tasks
+    /// are all waiting on each other and can never be woken.
+    pub fn run(&mut self) -> bool {
+        while !self.tasks.is_empty() {
+            let mut progressed = false;
+            let mut i = 0;
+            while i < self.tasks.len() {
+                let task = &mut self.tasks[i];
+                if task.woken.0.swap(false, Ordering::SeqCst) {
+                    progressed = true;
+                    let waker = Waker::from(task.woken.clone());
+                    let mut cx = Context::from_waker(&waker);
+                    if task.future.as_mut().poll(&mut cx).is_ready() {
+                        self.tasks.remove(i);
+                        continue;
+                    }
+                }
+                i += 1;
+            }
+            if !progressed {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+#[test]
+fn test_executor() {
+    // doubles every input forever
+    let double = &[3, 11, 1002, 11, 2, 11, 4, 11, 1105, 1, 0, 0];
+
+    let requests = Pipe::new();
+    let responses = Pipe::new();
+    let received = Rc::new(RefCell::new(Vec::new()));
+
+    let mut executor = Executor::new();
+    let mut computer = Computer::with_io(double, requests.clone(), responses.clone());
+    let closed = responses.clone();
+    executor.spawn(async move {
+        assert!(!computer.run_async().await);
+        closed.close();
+    });
+    let sink = received.clone();
+    executor.spawn(async move {
+        while let Some(value) = responses.recv().await {
+            sink.borrow_mut().push(value);
+        }
+    });
+    executor.spawn(async move {
+        (1..=3).for_each(|value| requests.send(value));
+        requests.close();
+    });
+    assert!(executor.run());
+    assert_eq!(*received.borrow(), vec![2, 4, 6]);
+
+    // a breakpoint before the first read doesn't count as waiting for input
+    let mut isa = super::InstructionSet::new();
+    isa.register(11, 1, super::isa::breakpoint).unwrap();
+    let (input, output) = (Pipe::new(), Pipe::new());
+    input.send(5);
+    input.close();
+    let mut computer = Computer::with_io(&[11, 3, 0, 4, 0, 99], input, output.clone());
+    computer.set_instruction_set(Arc::new(isa));
+    let mut executor = Executor::new();
+    executor.spawn(async move {
+        assert!(computer.run_async().await);
+    });
+    assert!(executor.run());
+    assert_eq!(output.try_recv(), Some(5));
+
+    let stuck = Pipe::new();
+    let mut executor = Executor::new();
+    executor.spawn(async move {
+        stuck.recv().await;
+    });
+    assert!(!executor.run());
+}
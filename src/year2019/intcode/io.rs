@@ -0,0 +1,208 @@
+use super::Word;
+use std::collections::VecDeque;
+use std::io::{self, BufRead, Write};
+use std::sync::mpsc::{Receiver, Sender};
+
+/// Source of values for the `In` instruction
+pub trait Input {
+    /// Produce the next value, or `None` to make the machine yield until more input arrives
+    fn next_input(&mut self) -> Option<Word>;
+}
+
+/// Sink for values produced by the `Out` instruction
+pub trait Output {
+    fn push_output(&mut self, value: Word);
+
+    /// Drop values kept from an earlier run, when `Computer::reset` reloads
+    /// the machine. Sinks that pass values straight on keep nothing to drop.
+    fn clear_output(&mut self) {}
+}
+
+impl Input for VecDeque<Word> {
+    fn next_input(&mut self) -> Option<Word> {
+        self.pop_front()
+    }
+}
+
+/// Closures compute each input on demand, e.g. by sensing the machine's surroundings
+impl<F: FnMut() -> Option<Word>> Input for F {
+    fn next_input(&mut self) -> Option<Word> {
+        self()
+    }
+}
+
+/// Blocks until a value is sent, yielding once every sender has hung up
+impl Input for Receiver<Word> {
+    fn next_input(&mut self) -> Option<Word> {
+        self.recv().ok()
+    }
+}
+
+/// Feed the machine from any iterator of words
+pub struct IterInput<I>(pub I);
+
+impl<I: Iterator<Item = Word>> Input for IterInput<I> {
+    fn next_input(&mut self) -> Option<Word> {
+        self.0.next()
+    }
+}
+
+/// Read words separated by commas or whitespace from a buffered reader.
+/// Reading stops at the first I/O error or malformed word, which the machine
+/// sees as running out of input; check `error` to tell the two apart.
+pub struct ReadInput<R> {
+    reader: R,
+    pending: VecDeque<Word>,
+    error: Option<io::Error>,
+}
+
+impl<R: BufRead> ReadInput<R> {
+    pub fn new(reader: R) -> Self {
+        Self {
+            reader,
+            pending: VecDeque::new(),
+            error: None,
+        }
+    }
+
+    pub fn error(&self) -> Option<&io::Error> {
+        self.error.as_ref()
+    }
+}
+
+impl<R: BufRead> Input for ReadInput<R> {
+    fn next_input(&mut self) -> Option<Word> {
+        let mut line = String::new();
+        while self.pending.is_empty() && self.error.is_none() {
+            line.clear();
+            match self.reader.read_line(&mut line) {
+                Ok(0) => return None,
+                Ok(_) => {}
+                Err(err) => {
+                    self.error = Some(err);
+                    return None;
+                }
+            }
+            let words = line
+                .split(|c: char| c == ',' || c.is_whitespace())
+                .filter(|word| !word.is_empty());
+            for word in words {
+                match word.parse::<Word>() {
+                    Ok(value) => self.pending.push_back(value),
+                    Err(_) => {
+                        let message = format!("invalid word {:?}", word);
+                        self.error = Some(io::Error::new(io::ErrorKind::InvalidData, message));
+                        break;
+                    }
+                }
+            }
+        }
+        self.pending.pop_front()
+    }
+}
+
+impl Output for Vec<Word> {
+    fn push_output(&mut self, value: Word) {
+        self.push(value)
+    }
+
+    fn clear_output(&mut self) {
+        self.clear()
+    }
+}
+
+impl<F: FnMut(Word)> Output for F {
+    fn push_output(&mut self, value: Word) {
+        self(value)
+    }
+}
+
+/// Values sent after the receiver hangs up are dropped
+impl Output for Sender<Word> {
+    fn push_output(&mut self, value: Word) {
+        let _ = self.send(value);
+    }
+}
+
+/// Write each output on its own line. Output after the first write error is
+/// dropped, and the error kept for `error`.
+pub struct WriteOutput<W> {
+    writer: W,
+    error: Option<io::Error>,
+}
+
+impl<W: Write> WriteOutput<W> {
+    pub fn new(writer: W) -> Self {
+        Self {
+            writer,
+            error: None,
+        }
+    }
+
+    pub fn error(&self) -> Option<&io::Error> {
+        self.error.as_ref()
+    }
+}
+
+impl<W: Write> Output for WriteOutput<W> {
+    fn push_output(&mut self, value: Word) {
+        if self.error.is_none() {
+            if let Err(err) = writeln!(self.writer, "{}", value) {
+                self.error = Some(err);
+            }
+        }
+    }
+}
+
+#[test]
+fn test_read_input() {
+    let mut input = ReadInput::new("1,2\n\n 3 -4\n".as_bytes());
+    assert_eq!(input.next_input(), Some(1));
+    assert_eq!(input.next_input(), Some(2));
+    assert_eq!(input.next_input(), Some(3));
+    assert_eq!(input.next_input(), Some(-4));
+    assert_eq!(input.next_input(), None);
+}
+
+#[test]
+fn test_io_errors() {
+    struct Unplugged;
+
+    impl io::Read for Unplugged {
+        fn read(&mut self, _: &mut [u8]) -> io::Result<usize> {
+            Err(io::Error::other("unplugged"))
+        }
+    }
+
+    impl Write for Unplugged {
+        fn write(&mut self, _: &[u8]) -> io::Result<usize> {
+            Err(io::ErrorKind::BrokenPipe.into())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    let mut input = ReadInput::new("5\n".as_bytes());
+    assert_eq!(input.next_input(), Some(5));
+    assert_eq!(input.next_input(), None);
+    assert!(input.error().is_none());
+
+    let mut input = ReadInput::new(io::BufReader::new(Unplugged));
+    assert_eq!(input.next_input(), None);
+    assert_eq!(input.error().unwrap().to_string(), "unplugged");
+
+    // words before a malformed one still arrive, but nothing after it
+    let mut input = ReadInput::new("1 x2 3\n4\n".as_bytes());
+    assert_eq!(input.next_input(), Some(1));
+    assert_eq!(input.next_input(), None);
+    assert_eq!(input.next_input(), None);
+    assert_eq!(input.error().unwrap().kind(), io::ErrorKind::InvalidData);
+    assert_eq!(input.error().unwrap().to_string(), "invalid word \"x2\"");
+
+    let mut output = WriteOutput::new(Unplugged);
+    output.push_output(1);
+    output.push_output(2);
+    assert_eq!(output.error().unwrap().kind(), io::ErrorKind::BrokenPipe);
+}
@@ -0,0 +1,154 @@
+use super::Word;
+use std::collections::HashMap;
+
+/// What the machine does after a custom instruction runs
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Flow {
+    /// Advance past the instruction
+    Next,
+    Jump(Word),
+    /// Advance past the instruction, then return `Status::Break` to the caller
+    Break,
+    /// Stop with an exit code
+    Halt(Word),
+}
+
+/// Machine state a custom instruction can work with
+pub trait Context {
+    /// Read parameter `n`, counting from 1, honouring its mode
+    fn param(&mut self, n: u32) -> Word;
+    fn set_param(&mut self, n: u32, value: Word);
+    fn pc(&self) -> Word;
+    fn input(&mut self) -> Option<Word>;
+    fn output(&mut self, value: Word);
+}
+
+pub type Handler = fn(&mut dyn Context) -> Flow;
+
+#[derive(Clone, Copy, Debug)]
+pub struct Instruction {
+    /// Words taken by the opcode and its parameters
+    pub len: Word,
+    pub handler: Handler,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum RegisterError {
+    /// Only the two low digits select an instruction
+    OutOfRange(Word),
+    /// Already used by the AoC 2019 instructions
+    Builtin(Word),
+    AlreadyRegistered(Word),
+    /// Every instruction must take at least its opcode word
+    ZeroLength,
+}
+
+/// Instructions available beyond the AoC 2019 ones. The default set is empty,
+/// so unknown opcodes still panic exactly as before.
+#[derive(Clone, Debug, Default)]
+pub struct InstructionSet {
+    custom: HashMap<Word, Instruction>,
+}
+
+impl InstructionSet {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn register(
+        &mut self,
+        opcode: Word,
+        len: Word,
+        handler: Handler,
+    ) -> Result<(), RegisterError> {
+        if !(0..100).contains(&opcode) {
+            return Err(RegisterError::OutOfRange(opcode));
+        }
+        if super::Opcode::decode(opcode).is_some() {
+            return Err(RegisterError::Builtin(opcode));
+        }
+        if self.custom.contains_key(&opcode) {
+            return Err(RegisterError::AlreadyRegistered(opcode));
+        }
+        if len < 1 {
+            return Err(RegisterError::ZeroLength);
+        }
+        self.custom.insert(opcode, Instruction { len, handler });
+        Ok(())
+    }
+
+    pub fn get(&self, opcode: Word) -> Option<Instruction> {
+        self.custom.get(&opcode).cloned()
+    }
+}
+
+/// Print the first parameter to stderr; takes 2 words
+pub fn debug_print(ctx: &mut dyn Context) -> Flow {
+    let value = ctx.param(1);
+    eprintln!("intcode @{}: {}", ctx.pc(), value);
+    Flow::Next
+}
+
+/// Suspend execution so the caller can inspect the machine; takes 1 word
+pub fn breakpoint(_: &mut dyn Context) -> Flow {
+    Flow::Break
+}
+
+/// Halt with the first parameter as the exit code; takes 2 words
+pub fn halt_with_code(ctx: &mut dyn Context) -> Flow {
+    Flow::Halt(ctx.param(1))
+}
+
+#[test]
+fn test_instruction_set() {
+    use super::{Computer, Status};
+    use std::sync::Arc;
+
+    let mut isa = InstructionSet::new();
+    isa.register(10, 2, debug_print).unwrap();
+    isa.register(11, 1, breakpoint).unwrap();
+    isa.register(12, 2, halt_with_code).unwrap();
+    assert_eq!(
+        isa.register(11, 1, breakpoint),
+        Err(RegisterError::AlreadyRegistered(11))
+    );
+    assert_eq!(
+        isa.register(4, 2, breakpoint),
+        Err(RegisterError::Builtin(4))
+    );
+    assert_eq!(
+        isa.register(104, 2, breakpoint),
+        Err(RegisterError::OutOfRange(104))
+    );
+    assert_eq!(
+        isa.register(13, 0, breakpoint),
+        Err(RegisterError::ZeroLength)
+    );
+
+    let code = &[11, 104, 7, 110, 1, 112, 42];
+    let isa = Arc::new(isa);
+    let mut computer = Computer::with_program(code, &[]);
+    computer.set_instruction_set(isa.clone());
+    assert_eq!(computer.run(), Status::Break);
+    assert!(computer.get_output().is_empty());
+    assert_eq!(computer.run(), Status::Halted);
+    assert_eq!(computer.exit_code(), Some(42));
+    assert_eq!(*computer.get_output(), vec![7]);
+
+    // exec steps over breaks, so false only ever means waiting for input
+    let mut computer = Computer::with_program(code, &[]);
+    computer.set_instruction_set(isa);
+    assert!(computer.exec());
+    assert_eq!(*computer.get_output(), vec![7]);
+
+    // built-in programs are unaffected, and halt without a code
+    let mut computer = Computer::with_program(&[104, 1, 99], &[]);
+    assert_eq!(computer.run(), Status::Halted);
+    assert_eq!(computer.exit_code(), None);
+}
+
+#[test]
+#[should_panic(expected = "invalid opcode: 11")]
+fn test_unregistered_opcode() {
+    super::Computer::run_program(&[11, 99], &[]);
+}
@@ -0,0 +1,153 @@
+use super::{Computer, Input, Output, Status, Word};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::time::Duration;
+
+/// Why a networked machine stopped running
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Stop {
+    /// Reached the `End` instruction
+    Halted,
+    /// Its upstream machine stopped and every value it sent has been consumed
+    Starved,
+    /// Waited longer than the timeout for its next input
+    TimedOut,
+}
+
+/// At least one machine gave up waiting for input
+#[derive(Clone, Debug, PartialEq)]
+pub struct Deadlock {
+    pub stops: Vec<Stop>,
+}
+
+impl std::fmt::Display for Deadlock {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let stuck = self
+            .stops
+            .iter()
+            .enumerate()
+            .filter(|(_, stop)| **stop == Stop::TimedOut)
+            .map(|(i, _)| i.to_string())
+            .collect::<Vec<_>>();
+        write!(f, "deadlock: machines {} timed out", stuck.join(", "))
+    }
+}
+
+impl std::error::Error for Deadlock {}
+
+struct Link {
+    rx: Receiver<Word>,
+    timeout: Duration,
+    timed_out: bool,
+}
+
+impl Input for Link {
+    fn next_input(&mut self) -> Option<Word> {
+        match self.rx.recv_timeout(self.timeout) {
+            Ok(value) => Some(value),
+            Err(RecvTimeoutError::Timeout) => {
+                self.timed_out = true;
+                None
+            }
+            Err(RecvTimeoutError::Disconnected) => None,
+        }
+    }
+}
+
+/// Forward output downstream while keeping a copy, since the last machine's
+/// final values may arrive after its consumer has already halted
+struct Tee {
+    tx: Sender<Word>,
+    log: Vec<Word>,
+}
+
+impl Output for Tee {
+    fn push_output(&mut self, value: Word) {
+        self.log.push(value);
+        let _ = self.tx.send(value);
+    }
+}
+
+/// Run each `(code, input)` machine on its own thread, wired in a ring so
+/// machine `i` feeds machine `i + 1` and the last feeds back into the first.
+///
+/// When a machine halts its outgoing channel closes, so machines waiting on it
+/// drain what was sent and then stop instead of hanging. A machine left
+/// waiting longer than `timeout` is reported as a deadlock. Returns everything
+/// each machine output.
+pub fn run_ring(
+    machines: &[(&[Word], &[Word])],
+    timeout: Duration,
+) -> Result<Vec<Vec<Word>>, Deadlock> {
+    let (mut senders, receivers): (Vec<_>, Vec<_>) =
+        machines.iter().map(|_| mpsc::channel()).unzip();
+    for (tx, (_, input)) in senders.iter().zip(machines) {
+        input.iter().for_each(|&value| tx.send(value).unwrap());
+    }
+    senders.rotate_left(1);
+
+    let results = std::thread::scope(|scope| {
+        let handles = machines
+            .iter()
+            .zip(receivers)
+            .zip(senders)
+            .map(|((&(code, _), rx), tx)| {
+                scope.spawn(move || {
+                    let link = Link {
+                        rx,
+                        timeout,
+                        timed_out: false,
+                    };
+                    let tee = Tee {
+                        tx,
+                        log: Vec::new(),
+                    };
+                    let mut computer = Computer::with_io(code, link, tee);
+                    let status = loop {
+                        match computer.run() {
+                            Status::Break => continue,
+                            status => break status,
+                        }
+                    };
+                    let (link, tee) = computer.into_io();
+                    let stop = match status {
+                        Status::Halted => Stop::Halted,
+                        _ if link.timed_out => Stop::TimedOut,
+                        _ => Stop::Starved,
+                    };
+                    (stop, tee.log)
+                })
+            })
+            .collect::<Vec<_>>();
+        handles
+            .into_iter()
+            .map(|handle| handle.join().unwrap())
+            .collect::<Vec<_>>()
+    });
+
+    let (stops, outputs): (Vec<_>, Vec<_>) = results.into_iter().unzip();
+    if stops.contains(&Stop::TimedOut) {
+        Err(Deadlock { stops })
+    } else {
+        Ok(outputs)
+    }
+}
+
+#[test]
+fn test_run_ring() {
+    // adds one to each value passing through, stopping after three
+    let inc = &[
+        3, 20, 1001, 20, 1, 20, 4, 20, 1001, 21, -1, 21, 1005, 21, 0, 99, 0, 0, 0, 0, 0, 3,
+    ][..];
+
+    let outputs = run_ring(&[(inc, &[0]), (inc, &[])], Duration::from_secs(1)).unwrap();
+    assert_eq!(outputs, vec![vec![1, 3, 5], vec![2, 4, 6]]);
+
+    let echo = &[3, 0, 4, 0, 99][..];
+    // whichever machine times out first closes its channel, starving the other
+    let stalled = run_ring(&[(echo, &[]), (echo, &[])], Duration::from_millis(50)).unwrap_err();
+    assert!(stalled.stops.contains(&Stop::TimedOut));
+    assert!(!stalled.stops.contains(&Stop::Halted));
+
+    let starved = run_ring(&[(echo, &[7]), (inc, &[])], Duration::from_secs(1));
+    assert_eq!(starved, Ok(vec![vec![7], vec![8]]));
+}
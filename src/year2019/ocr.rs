@@ -0,0 +1,240 @@
+use crate::year2019::canvas::Canvas;
+
+/// Glyphs of the 6 pixel high AoC font, mostly 4 wide, with rows separated
+/// by spaces
+static FONT_6: &[(char, &str)] = &[
+    ('A', ".##. #..# #..# #### #..# #..#"),
+    ('B', "###. #..# ###. #..# #..# ###."),
+    ('C', ".##. #..# #... #... #..# .##."),
+    ('E', "#### #... ###. #... #... ####"),
+    ('F', "#### #... ###. #... #... #..."),
+    ('G', ".##. #..# #... #.## #..# .###"),
+    ('H', "#..# #..# #### #..# #..# #..#"),
+    ('I', "### .#. .#. .#. .#. ###"),
+    ('J', "..## ...# ...# ...# #..# .##."),
+    ('K', "#..# #.#. ##.. #.#. #.#. #..#"),
+    ('L', "#... #... #... #... #... ####"),
+    ('O', ".##. #..# #..# #..# #..# .##."),
+    ('P', "###. #..# #..# ###. #... #..."),
+    ('R', "###. #..# #..# ###. #.#. #..#"),
+    ('S', ".### #... #... .##. ...# ###."),
+    ('U', "#..# #..# #..# #..# #..# .##."),
+    ('Y', "#...# #...# .#.#. ..#.. ..#.. ..#.."),
+    ('Z', "#### ...# ..#. .#.. #... ####"),
+];
+
+/// Glyphs of the 10 pixel high AoC font, 6 wide
+static FONT_10: &[(char, &str)] = &[
+    (
+        'A',
+        "..##.. .#..#. #....# #....# #....# ###### #....# #....# #....# #....#",
+    ),
+    (
+        'B',
+        "#####. #....# #....# #....# #####. #....# #....# #....# #....# #####.",
+    ),
+    (
+        'C',
+        ".####. #....# #..... #..... #..... #..... #..... #..... #....# .####.",
+    ),
+    (
+        'E',
+        "###### #..... #..... #..... #####. #..... #..... #..... #..... ######",
+    ),
+    (
+        'F',
+        "###### #..... #..... #..... #####. #..... #..... #..... #..... #.....",
+    ),
+    (
+        'G',
+        ".####. #....# #..... #..... #..... #..### #....# #....# #...## .###.#",
+    ),
+    (
+        'H',
+        "#....# #....# #....# #....# ###### #....# #....# #....# #....# #....#",
+    ),
+    (
+        'J',
+        "...### ....#. ....#. ....#. ....#. ....#. ....#. #...#. #...#. .###..",
+    ),
+    (
+        'K',
+        "#....# #...#. #..#.. #.#... ##.... ##.... #.#... #..#.. #...#. #....#",
+    ),
+    (
+        'L',
+        "#..... #..... #..... #..... #..... #..... #..... #..... #..... ######",
+    ),
+    (
+        'N',
+        "#....# ##...# ##...# #.#..# #.#..# #..#.# #..#.# #...## #...## #....#",
+    ),
+    (
+        'P',
+        "#####. #....# #....# #....# #####. #..... #..... #..... #..... #.....",
+    ),
+    (
+        'R',
+        "#####. #....# #....# #....# #####. #..#.. #...#. #...#. #....# #....#",
+    ),
+    (
+        'X',
+        "#....# #....# .#..#. .#..#. ..##.. ..##.. .#..#. .#..#. #....# #....#",
+    ),
+    (
+        'Z',
+        "###### .....# .....# ....#. ...#.. ..#... .#.... #..... #..... ######",
+    ),
+];
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum OcrError {
+    /// Lettering, once blank rows are trimmed, must match a font's height
+    UnsupportedHeight(usize),
+    /// Some glyphs matched no letter. `text` has `?` in their place and
+    /// `columns` holds where each one starts in the grid.
+    Unrecognised { text: String, columns: Vec<usize> },
+}
+
+impl std::fmt::Display for OcrError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            OcrError::UnsupportedHeight(height) => {
+                write!(f, "no font is {} pixels high", height)
+            }
+            OcrError::Unrecognised { text, columns } => {
+                let columns = columns.iter().map(usize::to_string).collect::<Vec<_>>();
+                write!(
+                    f,
+                    "unrecognised glyphs at columns {} in {:?}",
+                    columns.join(", "),
+                    text
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for OcrError {}
+
+/// Read block letters from lit pixels. Surrounding blank space is ignored
+/// and letters are split wherever a column is blank.
+pub fn recognize(canvas: &Canvas) -> Result<String, OcrError> {
+    let (xs, ys) = canvas.lit_bounds().ok_or(OcrError::UnsupportedHeight(0))?;
+    let font = match ys.len() {
+        6 => FONT_6,
+        10 => FONT_10,
+        height => return Err(OcrError::UnsupportedHeight(height)),
+    };
+
+    let lit = |x: usize| ys.clone().any(|y| canvas.get(x, y));
+    let mut text = String::new();
+    let mut unknown = Vec::new();
+    let mut x = xs.start;
+    while x < xs.end {
+        if !lit(x) {
+            x += 1;
+            continue;
+        }
+        let start = x;
+        while x < xs.end && lit(x) {
+            x += 1;
+        }
+        let glyph = ys
+            .clone()
+            .map(|y| (start..x).map(|x| canvas.get(x, y)).collect())
+            .collect::<Vec<Vec<bool>>>();
+        match font.iter().find(|(_, pattern)| glyph == trim(pattern)) {
+            Some(&(letter, _)) => text.push(letter),
+            None => {
+                text.push('?');
+                unknown.push(start);
+            }
+        }
+    }
+
+    if unknown.is_empty() {
+        Ok(text)
+    } else {
+        Err(OcrError::Unrecognised {
+            text,
+            columns: unknown,
+        })
+    }
+}
+
+/// A font pattern as pixels, without the blank columns around narrow letters
+fn trim(pattern: &str) -> Vec<Vec<bool>> {
+    let pattern = pattern.split(' ').collect::<Vec<_>>();
+    let lit = |x| pattern.iter().any(|row| row.as_bytes()[x] == b'#');
+    let width = pattern[0].len();
+    let start = (0..width).find(|&x| lit(x)).unwrap();
+    let end = (0..width).rfind(|&x| lit(x)).unwrap() + 1;
+    pattern
+        .iter()
+        .map(|row| {
+            row.as_bytes()[start..end]
+                .iter()
+                .map(|&b| b == b'#')
+                .collect()
+        })
+        .collect()
+}
+
+#[cfg(test)]
+fn render(font: &[(char, &str)], text: &str) -> Vec<Vec<bool>> {
+    let height = font[0].1.split(' ').count();
+    let mut rows = vec![vec![false]; height];
+    for letter in text.chars() {
+        let (_, pattern) = font.iter().find(|(c, _)| *c == letter).unwrap();
+        for (row, line) in rows.iter_mut().zip(pattern.split(' ')) {
+            row.extend(line.bytes().map(|b| b == b'#'));
+            row.extend([false, false]);
+        }
+    }
+    rows
+}
+
+#[test]
+fn test_recognize() {
+    for font in [FONT_6, FONT_10] {
+        let alphabet = font.iter().map(|(c, _)| *c).collect::<String>();
+        let canvas = Canvas::from_rows(&render(font, &alphabet));
+        assert_eq!(recognize(&canvas), Ok(alphabet));
+    }
+
+    let mut rows = render(FONT_6, "HI");
+    rows.insert(0, vec![false; 3]);
+    rows.push(Vec::new());
+    assert_eq!(recognize(&Canvas::from_rows(&rows)), Ok("HI".to_owned()));
+}
+
+#[test]
+fn test_unrecognised() {
+    let mut rows = render(FONT_6, "ABC");
+    // blot out the gap before B, merging it into A
+    rows[0][5] = true;
+    rows[0][6] = true;
+    rows.iter_mut().for_each(|row| row.extend([false, true]));
+    let canvas = Canvas::from_rows(&rows);
+    assert_eq!(
+        recognize(&canvas),
+        Err(OcrError::Unrecognised {
+            text: "?C?".to_owned(),
+            columns: vec![1, 20]
+        })
+    );
+    assert_eq!(
+        recognize(&canvas).unwrap_err().to_string(),
+        "unrecognised glyphs at columns 1, 20 in \"?C?\""
+    );
+
+    assert_eq!(
+        recognize(&Canvas::from_rows(&[[true, false], [false, true]])),
+        Err(OcrError::UnsupportedHeight(2))
+    );
+    assert_eq!(
+        recognize(&Canvas::new(3, 3)),
+        Err(OcrError::UnsupportedHeight(0))
+    );
+}
@@ -0,0 +1,20 @@
+/// Seeded pseudo-random numbers, so randomised tests see the same input on
+/// every run
+pub struct Rng {
+    state: u64,
+}
+
+impl Rng {
+    pub fn new(seed: u64) -> Rng {
+        Rng { state: seed }
+    }
+
+    /// A number in `0..n`, from the high bits of a 64-bit LCG
+    pub fn below(&mut self, n: usize) -> usize {
+        self.state = self
+            .state
+            .wrapping_mul(6364136223846793005)
+            .wrapping_add(1442695040888963407);
+        (self.state >> 33) as usize % n
+    }
+}